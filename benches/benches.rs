@@ -1,6 +1,6 @@
 use strobe_rs::{SecParam, Strobe};
 
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
 
 // Literally all these functions (besides ratchet) should have the same runtime. But a benchmark
 // can't hurt, I suppose
@@ -12,10 +12,10 @@ fn bench_nonmeta(c: &mut Criterion) {
     let mut v = [0u8; 256];
     g.bench_function("send_enc", |b| b.iter(|| s.send_enc(&mut v, false)));
     g.bench_function("recv_enc", |b| b.iter(|| s.recv_enc(&mut v, false)));
-    g.bench_function("send_clr", |b| b.iter(|| s.send_clr(&v, false)));
-    g.bench_function("recv_clr", |b| b.iter(|| s.recv_clr(&v, false)));
-    g.bench_function("ad", |b| b.iter(|| s.ad(&v, false)));
-    g.bench_function("key", |b| b.iter(|| s.key(&v, false)));
+    g.bench_function("send_clr", |b| b.iter(|| s.send_clr(v, false)));
+    g.bench_function("recv_clr", |b| b.iter(|| s.recv_clr(v, false)));
+    g.bench_function("ad", |b| b.iter(|| s.ad(v, false)));
+    g.bench_function("key", |b| b.iter(|| s.key(v, false)));
     g.bench_function("prf", |b| b.iter(|| s.prf(&mut v, false)));
     g.bench_function("send_mac", |b| b.iter(|| s.send_mac(&mut v, false)));
     g.bench_function("rachet 16", |b| b.iter(|| s.ratchet(16, false)));
@@ -32,10 +32,10 @@ fn bench_meta(c: &mut Criterion) {
     g.bench_function("meta_recv_enc", |b| {
         b.iter(|| s.meta_recv_enc(&mut v, false))
     });
-    g.bench_function("meta_send_clr", |b| b.iter(|| s.meta_send_clr(&v, false)));
-    g.bench_function("meta_recv_clr", |b| b.iter(|| s.meta_recv_clr(&v, false)));
-    g.bench_function("meta_ad", |b| b.iter(|| s.meta_ad(&v, false)));
-    g.bench_function("meta_key", |b| b.iter(|| s.meta_key(&v, false)));
+    g.bench_function("meta_send_clr", |b| b.iter(|| s.meta_send_clr(v, false)));
+    g.bench_function("meta_recv_clr", |b| b.iter(|| s.meta_recv_clr(v, false)));
+    g.bench_function("meta_ad", |b| b.iter(|| s.meta_ad(v, false)));
+    g.bench_function("meta_key", |b| b.iter(|| s.meta_key(v, false)));
     g.bench_function("meta_prf", |b| b.iter(|| s.meta_prf(&mut v, false)));
     g.bench_function("meta_send_mac", |b| {
         b.iter(|| s.meta_send_mac(&mut v, false))
@@ -43,5 +43,47 @@ fn bench_meta(c: &mut Criterion) {
     g.bench_function("meta_rachet 16", |b| b.iter(|| s.meta_ratchet(16, false)));
 }
 
-criterion_group!(benches, bench_nonmeta, bench_meta);
+// The per-byte loop overhead only shows up on big inputs, so bench a few of those too
+fn bench_large(c: &mut Criterion) {
+    let mut g = c.benchmark_group("large input benches");
+
+    let mut s = Strobe::new(b"largebench", SecParam::B256);
+    let mut v = vec![0u8; 1 << 16];
+    g.throughput(Throughput::Bytes(v.len() as u64));
+    g.bench_function("send_enc 64KiB", |b| b.iter(|| s.send_enc(&mut v, false)));
+    g.bench_function("ad 64KiB", |b| b.iter(|| s.ad(&v, false)));
+    g.bench_function("prf 64KiB", |b| b.iter(|| s.prf(&mut v, false)));
+}
+
+// Sweep the core ops over input sizes at both security levels. Sizes straddle the B256 rate (134
+// bytes) and the B128 rate (166 bytes), so the permutation cost shows up as well as the per-byte
+// cost.
+fn bench_sizes(c: &mut Criterion) {
+    let sizes = [16usize, 64, 256, 1024, 16384];
+
+    for (sec_name, sec) in [("B128", SecParam::B128), ("B256", SecParam::B256)] {
+        let mut g = c.benchmark_group(format!("sized benches {}", sec_name));
+        for size in sizes {
+            let mut s = Strobe::new(b"sizedbench", sec);
+            let mut v = vec![0u8; size];
+            g.throughput(Throughput::Bytes(size as u64));
+
+            g.bench_with_input(BenchmarkId::new("prf", size), &size, |b, _| {
+                b.iter(|| s.prf(&mut v, false))
+            });
+            g.bench_with_input(BenchmarkId::new("send_enc", size), &size, |b, _| {
+                b.iter(|| s.send_enc(&mut v, false))
+            });
+            g.bench_with_input(BenchmarkId::new("recv_enc", size), &size, |b, _| {
+                b.iter(|| s.recv_enc(&mut v, false))
+            });
+            g.bench_with_input(BenchmarkId::new("ratchet", size), &size, |b, &size| {
+                b.iter(|| s.ratchet(size, false))
+            });
+        }
+        g.finish();
+    }
+}
+
+criterion_group!(benches, bench_nonmeta, bench_meta, bench_large, bench_sizes);
 criterion_main!(benches);
@@ -20,8 +20,8 @@ fn main() {
     // Have the transmitter sample and send a nonce (192 bits) in the clear
     let mut nonce = [0u8; 24];
     rng.fill_bytes(&mut nonce);
-    rx.recv_clr(&nonce, false);
-    tx.send_clr(&nonce, false);
+    rx.recv_clr(nonce, false);
+    tx.send_clr(nonce, false);
 
     // Have the transmitter send an authenticated ciphertext (with a 256 bit MAC)
     let orig_msg = b"groceries: kaymac, ajvar, cream, diced onion, red pepper, grilled meat";
@@ -8,7 +8,9 @@
 
 use crate::{
     keccak::KECCAK_BLOCK_SIZE,
-    strobe::{SecParam, Strobe},
+    strobe::{
+        hash, hash_array, AuthError, OpFlags, SecParam, Strobe, StrobeError, KEYSTREAM_BLOCK_LEN,
+    },
 };
 
 /*
@@ -385,6 +387,19 @@ fn test_streaming_soundness_mutate() {
     s.send_enc(&mut msg, true);
 }
 
+// Same as above, but where the preceding operation is a different non-mutating one
+#[test]
+#[should_panic]
+fn test_streaming_soundness_mixed_ops() {
+    let mut s = Strobe::new(b"mactest", SecParam::B256);
+    s.key(b"secretsauce", false);
+
+    // Do a streaming send_enc op right after an AD op. This should fail
+    s.ad(b"some data", false);
+    let mut msg = *b"testing";
+    s.send_enc(&mut msg, true);
+}
+
 // Same as above but with ratchet
 #[test]
 #[should_panic]
@@ -446,3 +461,1776 @@ fn test_mac_correctness_and_soundness() {
     let bad_res = rx.recv_mac(&bad_mac.try_into().unwrap());
     assert!(bad_res.is_err());
 }
+
+// Test that the hash helpers agree with driving a Strobe by hand
+#[test]
+fn test_hash_helpers() {
+    let mut s = Strobe::new(b"hashtest", SecParam::B256);
+    s.ad(b"some data", false);
+    let mut expected = [0u8; 32];
+    s.prf(&mut expected, false);
+
+    let mut out = [0xffu8; 32];
+    hash(b"hashtest", b"some data", &mut out);
+    assert_eq!(out, expected);
+    assert_eq!(hash_array::<32>(b"hashtest", b"some data"), expected);
+
+    // Different domain separators give different outputs
+    assert_ne!(hash_array::<32>(b"otherhashtest", b"some data"), expected);
+}
+
+// Test that the Debug impl never prints the secret state
+#[cfg(feature = "std")]
+#[test]
+fn test_debug_redacts_state() {
+    let mut s = Strobe::new(b"debugtest", SecParam::B256);
+    s.key(b"secretsauce", false);
+    let debug_str = format!("{:?}", s);
+
+    assert!(debug_str.contains("[redacted; 200]"));
+    assert!(debug_str.contains("Strobe-Keccak-256/1600-v1.0.2"));
+
+    // No run of 3 consecutive state bytes appears in the output, either in decimal or in hex
+    for window in s.st.0.windows(3) {
+        assert!(!debug_str.contains(format!("{:?}", window).trim_matches(&['[', ']'][..])));
+        assert!(!debug_str.contains(format!("{:x?}", window).trim_matches(&['[', ']'][..])));
+    }
+}
+
+// Test that XORing the same keystream twice is the identity, and that keystream is just PRF
+#[test]
+fn test_keystream() {
+    let mut s = Strobe::new(b"keystreamtest", SecParam::B256);
+    s.key(b"the-combination-on-my-luggage", false);
+    let mut s2 = s.clone();
+    let mut s3 = s.clone();
+
+    let orig_msg = *b"scattered buffer contents";
+    let mut msg = orig_msg;
+
+    // The keystream doesn't depend on the initial contents of the output buffer
+    let mut ks1 = [0xaau8; 25];
+    let mut ks2 = [0x55u8; 25];
+    s.keystream(&mut ks1);
+    s2.keystream(&mut ks2);
+    assert_eq!(ks1, ks2);
+
+    msg.iter_mut().zip(ks1.iter()).for_each(|(m, k)| *m ^= k);
+    assert_ne!(msg, orig_msg);
+    msg.iter_mut().zip(ks2.iter()).for_each(|(m, k)| *m ^= k);
+    assert_eq!(msg, orig_msg);
+
+    // Keystream and PRF produce the same output and state
+    let mut prf_out = [0u8; 25];
+    s3.prf(&mut prf_out, false);
+    assert_eq!(prf_out, ks1);
+    assert_eq!(s3.st.0, s.st.0);
+}
+
+// Test that fill_bytes advances the state exactly like prf
+#[test]
+fn test_fill_bytes() {
+    let mut s1 = Strobe::new(b"fillbytestest", SecParam::B256);
+    s1.key(b"secretsauce", false);
+    let mut s2 = s1.clone();
+
+    let mut buf1 = [0u8; 100];
+    let mut buf2 = [0u8; 100];
+    s1.fill_bytes(&mut buf1);
+    s2.prf(&mut buf2, false);
+
+    assert_eq!(buf1, buf2);
+    assert_eq!(s1.st.0, s2.st.0);
+}
+
+// Test that prf_u64 and prf_u32 are little-endian PRF draws that advance the state like prf
+#[test]
+fn test_prf_ints() {
+    let mut s1 = Strobe::new(b"prfinttest", SecParam::B256);
+    s1.key(b"secretsauce", false);
+    let mut s2 = s1.clone();
+
+    let mut buf = [0u8; 8];
+    s2.prf(&mut buf, false);
+    let x = s1.prf_u64();
+    assert_eq!(x, u64::from_le_bytes(buf));
+    assert_eq!(x, 0x8108df24d15dbb9a);
+    assert_eq!(s1.st.0, s2.st.0);
+
+    let mut buf = [0u8; 4];
+    s2.prf(&mut buf, false);
+    assert_eq!(s1.prf_u32(), u32::from_le_bytes(buf));
+    assert_eq!(s1.st.0, s2.st.0);
+}
+
+// Test that prf_u64_be is the big-endian reading of the same bytes as prf_u64
+#[test]
+fn test_prf_u64_be() {
+    let mut s1 = Strobe::new(b"prfinttest", SecParam::B256);
+    s1.key(b"secretsauce", false);
+    let mut s2 = s1.clone();
+
+    let x = s1.prf_u64_be();
+    assert_eq!(x, s2.prf_u64().swap_bytes());
+    assert_eq!(x, 0x9abb5dd124df0881);
+    assert_eq!(s1.st.0, s2.st.0);
+}
+
+// Test that SecParam and OpFlags serialize as plain integers, and that invalid values are rejected
+#[cfg(feature = "serialize_secret_state")]
+#[test]
+fn test_serde_sec_param_and_flags() {
+    assert_eq!(serde_json::to_string(&SecParam::B128).unwrap(), "128");
+    assert_eq!(serde_json::to_string(&SecParam::B256).unwrap(), "256");
+    assert!(matches!(serde_json::from_str("256"), Ok(SecParam::B256)));
+    assert!(serde_json::from_str::<SecParam>("192").is_err());
+    assert!(serde_json::from_str::<SecParam>("0").is_err());
+    assert!(serde_json::from_str::<SecParam>("130").is_err());
+    assert!(serde_json::from_str::<SecParam>("1024").is_err());
+
+    let flags = OpFlags::A | OpFlags::C | OpFlags::T;
+    assert_eq!(serde_json::to_string(&flags).unwrap(), "14");
+    assert_eq!(serde_json::from_str::<OpFlags>("14").unwrap(), flags);
+    // Reserved and unknown flags are rejected
+    assert!(serde_json::from_str::<OpFlags>("32").is_err());
+    assert!(serde_json::from_str::<OpFlags>("64").is_err());
+}
+
+// Test that key_zeroizing wipes the key and otherwise acts like key
+#[test]
+fn test_key_zeroizing() {
+    let mut s1 = Strobe::new(b"keyzeroizingtest", SecParam::B256);
+    let mut s2 = s1.clone();
+
+    let mut key = *b"the-combination-on-my-luggage";
+    s1.key(key, false);
+    s2.key_zeroizing(&mut key);
+
+    assert_eq!(key, [0u8; 29]);
+    assert_eq!(s1.st.0, s2.st.0);
+}
+
+// Test that recv_enc_to_vec matches in-place recv_enc
+#[cfg(feature = "alloc")]
+#[test]
+fn test_recv_enc_to_vec() {
+    let mut s1 = Strobe::new(b"recvenctovectest", SecParam::B256);
+    s1.key(b"secretsauce", false);
+    let mut s2 = s1.clone();
+
+    let ciphertext = *b"not really a ciphertext";
+    let mut buf = ciphertext;
+    s1.recv_enc(&mut buf, false);
+    let plaintext = s2.recv_enc_to_vec(&ciphertext, false);
+
+    assert_eq!(plaintext, buf);
+    assert_eq!(s1.st.0, s2.st.0);
+}
+
+// Test that send_enc_to_vec matches in-place send_enc, and round-trips with recv_enc_to_vec
+#[cfg(feature = "alloc")]
+#[test]
+fn test_send_enc_to_vec() {
+    let mut s1 = Strobe::new(b"sendenctovectest", SecParam::B256);
+    s1.key(b"secretsauce", false);
+    let mut s2 = s1.clone();
+    let mut rx = s1.clone();
+
+    let plaintext = *b"attack at dawn";
+    let mut buf = plaintext;
+    s1.send_enc(&mut buf, false);
+    let ciphertext = s2.send_enc_to_vec(&plaintext, false);
+
+    assert_eq!(ciphertext, buf);
+    assert_eq!(s1.st.0, s2.st.0);
+    assert_eq!(rx.recv_enc_to_vec(&ciphertext, false), plaintext);
+}
+
+// Test that raw operate calls with the right flags match the typed methods
+#[test]
+fn test_raw_operate() {
+    let mut s1 = Strobe::new(b"rawoperatetest", SecParam::B256);
+    let mut s2 = s1.clone();
+
+    s1.ad(b"some data", false);
+    let mut data = *b"some data";
+    s2.operate(OpFlags::A, &mut data, false);
+    assert_eq!(&data, b"some data");
+    assert_eq!(s1.st.0, s2.st.0);
+
+    s1.meta_key(b"secretsauce", false);
+    let mut key = *b"secretsauce";
+    s2.operate(OpFlags::A | OpFlags::C | OpFlags::M, &mut key, false);
+    assert_eq!(s1.st.0, s2.st.0);
+
+    let mut buf1 = *b"attack at dawn";
+    let mut buf2 = buf1;
+    s1.send_enc(&mut buf1, false);
+    s2.operate(OpFlags::A | OpFlags::C | OpFlags::T, &mut buf2, false);
+    assert_eq!(buf1, buf2);
+    assert_eq!(s1.st.0, s2.st.0);
+}
+
+// The K flag is not implemented, so raw operate calls that use it must panic
+#[test]
+#[should_panic]
+fn test_raw_operate_k_flag() {
+    let mut s = Strobe::new(b"rawoperatetest", SecParam::B256);
+    s.operate(OpFlags::A | OpFlags::K, &mut [0u8; 8], false);
+}
+
+// Test that recv_mac failures propagate into a StrobeError with `?`
+#[test]
+fn test_mac_error_conversion() {
+    fn check_mac(s: &mut Strobe, mac: &[u8; 16]) -> Result<(), StrobeError> {
+        s.recv_mac(mac)?;
+        Ok(())
+    }
+
+    let mut s = Strobe::new(b"mactest", SecParam::B256);
+    s.key(b"secretsauce", false);
+    assert_eq!(check_mac(&mut s, &[0u8; 16]), Err(StrobeError::MacMismatch));
+}
+
+// Test that transcript tags agree exactly when the transcripts do, and don't modify the state
+#[test]
+fn test_transcript_tag() {
+    let mut s1 = Strobe::new(b"transcripttagtest", SecParam::B256);
+    let mut s2 = s1.clone();
+    s1.ad(b"hello", false);
+    s2.ad(b"hello", false);
+
+    let st_before = s1.st.0;
+    assert_eq!(s1.transcript_tag(), s2.transcript_tag());
+    assert_eq!(s1.st.0, st_before);
+
+    s2.ad(b"world", false);
+    assert_ne!(s1.transcript_tag(), s2.transcript_tag());
+}
+
+// Test that framing makes adjacent fields unambiguous, where plain AD is ambiguous
+#[test]
+fn test_framed_ops() {
+    let base = Strobe::new(b"framedtest", SecParam::B256);
+
+    // Plain AD can't tell "ab" + "c" from "a" + "bc", since both are streamed the same way
+    let mut s1 = base.clone();
+    let mut s2 = base.clone();
+    s1.ad(b"ab", false);
+    s1.ad(b"c", true);
+    s2.ad(b"a", false);
+    s2.ad(b"bc", true);
+    assert_eq!(s1.st.0, s2.st.0);
+
+    let mut s1 = base.clone();
+    let mut s2 = base.clone();
+    s1.ad_framed(b"ab");
+    s1.ad_framed(b"c");
+    s2.ad_framed(b"a");
+    s2.ad_framed(b"bc");
+    assert_ne!(s1.st.0, s2.st.0);
+
+    // The length prefix is 8 bytes, little-endian, and meta
+    let mut s3 = base.clone();
+    s3.meta_ad([2, 0, 0, 0, 0, 0, 0, 0], false);
+    s3.ad(b"ab", false);
+    s3.meta_ad([1, 0, 0, 0, 0, 0, 0, 0], false);
+    s3.ad(b"c", false);
+    assert_eq!(s1.st.0, s3.st.0);
+
+    // Framed send and receive stay in sync
+    let mut tx = base.clone();
+    let mut rx = base.clone();
+    tx.send_clr_framed(b"hello");
+    rx.recv_clr_framed(b"hello");
+    assert_eq!(tx.transcript_tag(), rx.transcript_tag());
+}
+
+// Test that derive_key is meta_ad followed by prf
+#[test]
+fn test_derive_key() {
+    let mut s1 = Strobe::new(b"derivekeytest", SecParam::B256);
+    s1.key(b"input keying material", false);
+    let mut s2 = s1.clone();
+
+    s1.meta_ad(b"info", false);
+    let mut expected = [0u8; 32];
+    s1.prf(&mut expected, false);
+
+    assert_eq!(s2.derive_key_array::<32>(b"info"), expected);
+    assert_eq!(s1.st.0, s2.st.0);
+}
+
+// Test that each peer's sending half talks to the other peer's receiving half
+#[test]
+fn test_split() {
+    let mut a = Strobe::new(b"splittest", SecParam::B256);
+    let mut b = Strobe::new(b"splittest", SecParam::B256);
+
+    // A little handshake. A is the initiator
+    a.key(b"shared secret", false);
+    b.key(b"shared secret", false);
+    a.send_clr(b"hello", false);
+    b.recv_clr(b"hello", false);
+
+    let (mut a_send, mut a_recv) = a.split();
+    let (mut b_send, mut b_recv) = b.split();
+
+    // A -> B
+    let orig_msg = *b"attack at dawn";
+    let mut msg = orig_msg;
+    a_send.send_enc(&mut msg, false);
+    b_recv.recv_enc(&mut msg, false);
+    assert_eq!(msg, orig_msg);
+
+    // B -> A
+    let orig_msg = *b"retreat at dusk";
+    let mut msg = orig_msg;
+    b_send.send_enc(&mut msg, false);
+    a_recv.recv_enc(&mut msg, false);
+    assert_eq!(msg, orig_msg);
+
+    // The two directions are independent
+    assert_ne!(a_send.transcript_tag(), a_recv.transcript_tag());
+}
+
+// Test that framed AE round-trips, and that tampered or truncated frames are rejected
+#[cfg(feature = "alloc")]
+#[test]
+fn test_seal_open_framed() {
+    let mut tx = Strobe::new(b"framedaetest", SecParam::B256);
+    tx.key(b"secretsauce", false);
+    let rx = tx.clone();
+
+    let msg = b"groceries: kaymac, ajvar, cream";
+    let frame = tx.seal_framed(msg);
+    assert_eq!(frame.len(), 8 + msg.len() + 16);
+    assert_eq!(&frame[..8], &(msg.len() as u64).to_be_bytes());
+
+    assert_eq!(rx.clone().open_framed(&frame).unwrap(), msg);
+
+    // Flip a ciphertext bit
+    let mut bad_frame = frame.clone();
+    bad_frame[10] ^= 1;
+    assert_eq!(
+        rx.clone().open_framed(&bad_frame),
+        Err(StrobeError::MacMismatch)
+    );
+
+    // Truncate the frame, with and without fixing up the length
+    let truncated = &frame[..frame.len() - 1];
+    assert_eq!(
+        rx.clone().open_framed(truncated),
+        Err(StrobeError::MacMismatch)
+    );
+    let mut truncated = frame[..frame.len() - 1].to_vec();
+    truncated[..8].copy_from_slice(&(msg.len() as u64 - 1).to_be_bytes());
+    assert_eq!(
+        rx.clone().open_framed(&truncated),
+        Err(StrobeError::MacMismatch)
+    );
+
+    // Declared lengths longer than the frame, and frames too short to hold anything, don't panic
+    let mut too_long = frame.clone();
+    too_long[..8].copy_from_slice(&u64::MAX.to_be_bytes());
+    assert_eq!(
+        rx.clone().open_framed(&too_long),
+        Err(StrobeError::MacMismatch)
+    );
+    assert_eq!(
+        rx.clone().open_framed(&frame[..5]),
+        Err(StrobeError::MacMismatch)
+    );
+}
+
+// Test that a frame with a bad MAC is rejected only after the full receive, which leaves the state
+// where the same operations done by hand leave it
+#[cfg(feature = "alloc")]
+#[test]
+fn test_open_framed_bad_mac() {
+    let mut tx = Strobe::new(b"framedaetest", SecParam::B256);
+    tx.key(b"secretsauce", false);
+    let mut rx = tx.clone();
+    let mut manual = tx.clone();
+
+    let msg = b"groceries: kaymac, ajvar, cream";
+    let mut frame = tx.seal_framed(msg);
+    let last = frame.len() - 1;
+    frame[last] ^= 1;
+    assert_eq!(rx.open_framed(&frame), Err(StrobeError::MacMismatch));
+
+    let mut ciphertext = frame[8..last - 15].to_vec();
+    manual.meta_ad(&frame[..8], false);
+    manual.recv_enc(&mut ciphertext, false);
+    assert_eq!(&ciphertext, msg);
+    let mac: &[u8; 16] = frame[last - 15..].try_into().unwrap();
+    assert!(manual.recv_mac(mac).is_err());
+    assert_eq!(rx.transcript_tag(), manual.transcript_tag());
+}
+
+// Test that a full ratchet is a ratchet of exactly `rate` bytes
+#[test]
+fn test_ratchet_full() {
+    let mut s1 = Strobe::new(b"ratchetfulltest", SecParam::B256);
+    s1.key(b"secretsauce", false);
+    let mut s2 = s1.clone();
+
+    s1.ratchet_full();
+    // The rate of a B256 Strobe is 200 - 256/4 - 2 = 134
+    s2.ratchet(134, false);
+    assert_eq!(s1.st.0, s2.st.0);
+}
+
+// Test that an explicitly set direction acts like one set by a transport op, and then is locked
+#[test]
+fn test_set_receiver() {
+    let mut s1 = Strobe::new(b"setreceivertest", SecParam::B256);
+    let mut s2 = s1.clone();
+    assert_eq!(s1.is_receiver(), None);
+
+    // s1 becomes the receiver implicitly, s2 explicitly
+    s1.recv_clr(b"hello", false);
+    s2.set_receiver(true).unwrap();
+    s2.recv_clr(b"hello", false);
+    assert_eq!(s1.is_receiver(), Some(true));
+    assert_eq!(s2.is_receiver(), Some(true));
+    assert_eq!(s1.st.0, s2.st.0);
+
+    // Now sending as s2 should be seen as the receiver sending
+    s1.send_clr(b"world", false);
+    s2.send_clr(b"world", false);
+    assert_eq!(s1.st.0, s2.st.0);
+
+    // The direction is locked
+    assert_eq!(s1.set_receiver(false), Err(StrobeError::DirectionLocked));
+    assert_eq!(s2.set_receiver(true), Err(StrobeError::DirectionLocked));
+}
+
+// Test that the GenericArray variants match the slice-based ones byte for byte
+#[cfg(feature = "generic-array")]
+#[test]
+fn test_generic_array_outputs() {
+    use generic_array::typenum::{U16, U32};
+
+    let mut s1 = Strobe::new(b"genericarraytest", SecParam::B256);
+    s1.key(b"secretsauce", false);
+    let mut s2 = s1.clone();
+
+    let mut key = [0u8; 32];
+    s1.prf(&mut key, false);
+    assert_eq!(s2.prf_generic::<U32>().as_slice(), &key);
+
+    let mut mac = [0u8; 16];
+    s1.send_mac(&mut mac, false);
+    assert_eq!(s2.send_mac_generic::<U16>().as_slice(), &mac);
+    assert_eq!(s1.st.0, s2.st.0);
+}
+
+// Test that the integer AD helpers use the encodings they say they do
+#[test]
+fn test_ad_ints() {
+    let mut s1 = Strobe::new(b"adinttest", SecParam::B256);
+    let mut s2 = s1.clone();
+
+    s1.ad_u64_le(0x0102030405060708);
+    s1.meta_ad_u32_le(0x01020304);
+    s1.ad_u64_be(0x0102030405060708);
+    s1.meta_ad_u32_be(0x01020304);
+
+    s2.ad([8, 7, 6, 5, 4, 3, 2, 1], false);
+    s2.meta_ad([4, 3, 2, 1], false);
+    s2.ad([1, 2, 3, 4, 5, 6, 7, 8], false);
+    s2.meta_ad([1, 2, 3, 4], false);
+
+    assert_eq!(s1.st.0, s2.st.0);
+
+    // 128-bit integers, e.g., UUIDs
+    let x = 0x0102030405060708090a0b0c0d0e0f10u128;
+    let bytes = [1u8, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
+    let mut rev = bytes;
+    rev.reverse();
+    s1.ad_u128_le(x);
+    s1.meta_ad_u128_be(x);
+    s2.ad(rev, false);
+    s2.meta_ad(bytes, false);
+
+    assert_eq!(s1.st.0, s2.st.0);
+}
+
+// Seeking to offset K should give the same bytes as reading K bytes and then the next chunk
+#[test]
+fn test_keystream_at_seek() {
+    let mut s = Strobe::new(b"keystream_at", SecParam::B128);
+    s.key(b"some key", false);
+    let before = s.clone();
+
+    let mut full = [0u8; 3 * KEYSTREAM_BLOCK_LEN + 17];
+    s.keystream_at(0, &mut full);
+
+    for k in [0, 1, 63, 64, 65, 100, 128, 3 * KEYSTREAM_BLOCK_LEN] {
+        for len in [0, 1, 30, 64, 70] {
+            let len = core::cmp::min(len, full.len() - k);
+            let mut chunk = [0u8; 70];
+            s.keystream_at(k as u64, &mut chunk[..len]);
+            assert_eq!(
+                &chunk[..len],
+                &full[k..k + len],
+                "offset {}, len {}",
+                k,
+                len
+            );
+        }
+    }
+
+    // The state must be untouched
+    assert_eq!(s.transcript_tag(), before.transcript_tag());
+
+    // Each block is a labeled prf on a copy of the state, which differs from an unlabeled one
+    let mut block = [0u8; KEYSTREAM_BLOCK_LEN];
+    let mut labeled = s.clone();
+    labeled.meta_ad(b"keystream", false);
+    labeled.meta_ad(2u64.to_le_bytes(), false);
+    labeled.prf(&mut block, false);
+    assert_eq!(
+        &block,
+        &full[2 * KEYSTREAM_BLOCK_LEN..3 * KEYSTREAM_BLOCK_LEN]
+    );
+
+    let mut unlabeled = s.clone();
+    unlabeled.meta_ad(2u64.to_le_bytes(), false);
+    unlabeled.prf(&mut block, false);
+    assert_ne!(
+        &block,
+        &full[2 * KEYSTREAM_BLOCK_LEN..3 * KEYSTREAM_BLOCK_LEN]
+    );
+}
+
+// Equality should cover the secret state and the bookkeeping fields
+#[cfg(feature = "test-util")]
+#[test]
+fn test_partial_eq() {
+    let mut s1 = Strobe::new(b"partial_eq", SecParam::B128);
+    let mut s2 = s1.clone();
+    assert_eq!(s1, s2);
+
+    s1.ad(b"hello", false);
+    assert_ne!(s1, s2);
+    s2.ad(b"hello", false);
+    assert_eq!(s1, s2);
+
+    // Same state bytes, different direction
+    let mut s3 = s1.clone();
+    s3.set_receiver(true).unwrap();
+    assert_ne!(s1, s3);
+}
+
+// new_with_salt should be new followed by meta_ad, and distinct salts should diverge
+#[test]
+fn test_new_with_salt() {
+    let salted = Strobe::new_with_salt(b"salted", b"salt A", SecParam::B256);
+
+    let mut manual = Strobe::new(b"salted", SecParam::B256);
+    manual.meta_ad(b"salt A", false);
+    assert_eq!(salted.transcript_tag(), manual.transcript_tag());
+
+    let other = Strobe::new_with_salt(b"salted", b"salt B", SecParam::B256);
+    assert_ne!(salted.transcript_tag(), other.transcript_tag());
+}
+
+// to_bytes/from_bytes should round-trip the full state, including bookkeeping
+#[cfg(all(feature = "serialize_secret_state", feature = "alloc"))]
+#[test]
+fn test_to_bytes_round_trip() {
+    let mut s = Strobe::new(b"to_bytes", SecParam::B128);
+    s.key(b"secret", false);
+    let mut msg = *b"hello";
+    s.send_enc(&mut msg, false);
+    // Serialize in the middle of a streaming op, so prev_flags matters
+    s.ad(b"streamed", false);
+
+    let bytes = s.to_bytes();
+    let mut restored = Strobe::from_bytes(&bytes).unwrap();
+    assert_eq!(restored.to_bytes()[..], bytes[..]);
+
+    // The restored state should be able to continue the streaming op
+    s.ad(b" more", true);
+    restored.ad(b" more", true);
+    assert_eq!(s.transcript_tag(), restored.transcript_tag());
+
+    // Malformed inputs are rejected
+    assert_eq!(
+        Strobe::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err(),
+        StrobeError::InvalidState
+    );
+    // A malformed field with a valid checksum is invalid. The has_prev_flags byte is 15 bytes
+    // from the end.
+    let mut bad = bytes.clone();
+    let n = bad.len();
+    bad[n - 15] = 3;
+    let checksum = crate::strobe::crc32(&bad[..n - 4]);
+    bad[n - 4..].copy_from_slice(&checksum.to_le_bytes());
+    assert_eq!(
+        Strobe::from_bytes(&bad).unwrap_err(),
+        StrobeError::InvalidState
+    );
+
+    // Flipping any bit without fixing the checksum is caught as corruption
+    for i in [0, 100, 199, 200, n - 5, n - 1] {
+        let mut corrupted = bytes.clone();
+        corrupted[i] ^= 0x10;
+        assert_eq!(
+            Strobe::from_bytes(&corrupted).unwrap_err(),
+            StrobeError::CorruptState,
+            "byte {}",
+            i
+        );
+    }
+}
+
+// Check the CRC-32 implementation against the standard check value
+#[cfg(all(feature = "serialize_secret_state", feature = "alloc"))]
+#[test]
+fn test_crc32() {
+    assert_eq!(crate::strobe::crc32(b"123456789"), 0xcbf4_3926);
+    assert_eq!(crate::strobe::crc32(b""), 0);
+}
+
+// Absorbing from a reader should be the same as a one-shot ad, regardless of how the reader
+// splits the data
+#[cfg(feature = "std")]
+#[test]
+fn test_ad_from_reader() {
+    use std::io::{Cursor, Read};
+
+    for len in [0, 1, 511, 512, 513, 2000] {
+        let data: std::vec::Vec<u8> = (0..len).map(|i| i as u8).collect();
+
+        let mut s1 = Strobe::new(b"ad_from_reader", SecParam::B128);
+        let read = s1.ad_from_reader(&mut Cursor::new(&data)).unwrap();
+        assert_eq!(read, len as u64);
+
+        // A reader that returns short reads
+        let mut s2 = Strobe::new(b"ad_from_reader", SecParam::B128);
+        let mut chained = Cursor::new(&data[..len / 3]).chain(Cursor::new(&data[len / 3..]));
+        s2.ad_from_reader(&mut chained).unwrap();
+
+        let mut s3 = Strobe::new(b"ad_from_reader", SecParam::B128);
+        s3.ad(&data, false);
+
+        assert_eq!(s1.transcript_tag(), s3.transcript_tag());
+        assert_eq!(s2.transcript_tag(), s3.transcript_tag());
+    }
+}
+
+// Encrypting and decrypting through streams should round-trip, and match the one-shot ops
+#[cfg(feature = "std")]
+#[test]
+fn test_enc_stream_round_trip() {
+    use std::io::Cursor;
+
+    for len in [0, 1, 512, 513, 3000] {
+        let plaintext: std::vec::Vec<u8> = (0..len).map(|i| (i * 7) as u8).collect();
+
+        let mut tx = Strobe::new(b"enc_stream", SecParam::B128);
+        let mut rx = Strobe::new(b"enc_stream", SecParam::B128);
+        tx.key(b"key", false);
+        rx.key(b"key", false);
+
+        let mut ciphertext = std::vec::Vec::new();
+        let n = tx
+            .send_enc_stream(&mut Cursor::new(&plaintext), &mut ciphertext)
+            .unwrap();
+        assert_eq!(n, len as u64);
+
+        let mut decrypted = std::vec::Vec::new();
+        rx.recv_enc_stream(&mut Cursor::new(&ciphertext), &mut decrypted)
+            .unwrap();
+        assert_eq!(decrypted, plaintext);
+
+        // Compare against a one-shot send_enc
+        let mut one_shot = Strobe::new(b"enc_stream", SecParam::B128);
+        one_shot.key(b"key", false);
+        let mut buf = plaintext.clone();
+        one_shot.send_enc(&mut buf, false);
+        assert_eq!(buf, ciphertext);
+        assert_eq!(one_shot.transcript_tag(), tx.transcript_tag());
+
+        // And the two sides should agree
+        let mut mac = [0u8; 16];
+        tx.send_mac(&mut mac, false);
+        assert!(rx.recv_mac(&mac).is_ok());
+    }
+}
+
+// The rate formula should match the Strobe spec, and reject invalid parameters
+#[test]
+fn test_sec_param_rate() {
+    assert_eq!(SecParam::B128.rate(200), Some(166));
+    assert_eq!(SecParam::B256.rate(200), Some(134));
+    assert_eq!(SecParam::B128.rate(100), Some(66));
+    assert_eq!(SecParam::B256.rate(66), None);
+    // The rate has to fit in a byte with room for the +2
+    assert_eq!(SecParam::B128.rate(300), None);
+
+    // Usable in const contexts
+    const R: Option<usize> = SecParam::B128.rate(200);
+    assert_eq!(R, Some(166));
+}
+
+// recv_mac_ct should agree with recv_mac, and its results should be combinable
+#[cfg(feature = "subtle")]
+#[test]
+fn test_recv_mac_ct() {
+    let mut tx = Strobe::new(b"recv_mac_ct", SecParam::B128);
+    let mut rx = Strobe::new(b"recv_mac_ct", SecParam::B128);
+
+    let mut mac1 = [0u8; 16];
+    let mut mac2 = [0u8; 16];
+    tx.send_mac(&mut mac1, false);
+    tx.meta_send_mac(&mut mac2, false);
+
+    // Check a good MAC on a copy, and a bad MAC on another copy
+    let mut rx_bad = rx.clone();
+    let mut bad_mac = mac1;
+    bad_mac[3] ^= 1;
+    assert!(!bool::from(rx_bad.recv_mac_ct(&bad_mac)));
+
+    let ok1 = rx.recv_mac_ct(&mac1);
+    let ok2 = rx.meta_recv_mac_ct(&mac2);
+    assert!(bool::from(ok1 & ok2));
+}
+
+// The public constants should agree with each other and with the rate of an actual Strobe
+#[test]
+fn test_public_consts() {
+    use crate::{rate_for, KECCAK_BLOCK_BYTES, STROBE_VERSION};
+
+    assert_eq!(KECCAK_BLOCK_BYTES, 8 * KECCAK_BLOCK_SIZE);
+    assert_eq!(STROBE_VERSION, b"1.0.2");
+
+    const R128: usize = rate_for(SecParam::B128);
+    const R256: usize = rate_for(SecParam::B256);
+    assert_eq!(R128, 166);
+    assert_eq!(R256, 134);
+    assert_eq!(
+        rate_for(SecParam::B256),
+        SecParam::B256.rate(KECCAK_BLOCK_BYTES).unwrap()
+    );
+}
+
+// A zero-length MAC should never verify
+#[test]
+fn test_empty_mac_rejected() {
+    let mut tx = Strobe::new(b"empty mac", SecParam::B128);
+    let mut rx = Strobe::new(b"empty mac", SecParam::B128);
+
+    let mut mac = [0u8; 0];
+    tx.send_mac(&mut mac, false);
+    assert_eq!(rx.clone().recv_mac(&mac), Err(AuthError));
+    assert_eq!(rx.meta_recv_mac(&mac), Err(AuthError));
+
+    #[cfg(feature = "subtle")]
+    {
+        let mut rx = Strobe::new(b"empty mac", SecParam::B128);
+        assert!(!bool::from(rx.clone().recv_mac_ct(&mac)));
+        assert!(!bool::from(rx.meta_recv_mac_ct(&mac)));
+    }
+}
+
+// send_mac_checked should enforce the minimum length, and otherwise match send_mac
+#[cfg(feature = "alloc")]
+#[test]
+fn test_send_mac_checked() {
+    use crate::strobe::MIN_MAC_LEN;
+
+    let mut s1 = Strobe::new(b"send_mac_checked", SecParam::B128);
+    let mut s2 = s1.clone();
+
+    // Too short. The state is left alone.
+    let before = s1.transcript_tag();
+    assert_eq!(
+        s1.send_mac_checked(MIN_MAC_LEN - 1),
+        Err(StrobeError::MacTooShort)
+    );
+    assert_eq!(s1.transcript_tag(), before);
+
+    let mac = s1.send_mac_checked(32).unwrap();
+    let mut expected = [0u8; 32];
+    s2.send_mac(&mut expected, false);
+    assert_eq!(mac, expected);
+
+    // The escape hatch
+    assert_eq!(s1.send_mac_allow_short(1).len(), 1);
+}
+
+// A custom rate should still make a working, but non-standard, Strobe
+#[test]
+fn test_new_with_rate() {
+    for rate in [1, 17, 166, 198] {
+        let mut tx = Strobe::new_with_rate(b"custom rate", SecParam::B128, rate).unwrap();
+        let mut rx = Strobe::new_with_rate(b"custom rate", SecParam::B128, rate).unwrap();
+        tx.key(b"key", false);
+        rx.key(b"key", false);
+
+        let orig_msg = [0x5au8; 500];
+        let mut msg = orig_msg;
+        tx.send_enc(&mut msg, false);
+        rx.recv_enc(&mut msg, false);
+        assert_eq!(msg, orig_msg);
+
+        let mut mac = [0u8; 16];
+        tx.send_mac(&mut mac, false);
+        assert!(rx.recv_mac(&mac).is_ok());
+    }
+
+    // Using the standard rate gives a standard Strobe
+    let standard = Strobe::new(b"custom rate", SecParam::B128);
+    let same = Strobe::new_with_rate(b"custom rate", SecParam::B128, 166).unwrap();
+    let other = Strobe::new_with_rate(b"custom rate", SecParam::B128, 190).unwrap();
+    assert_eq!(standard.transcript_tag(), same.transcript_tag());
+    assert_ne!(standard.transcript_tag(), other.transcript_tag());
+
+    // Invalid rates
+    for rate in [0, 199, 253] {
+        assert_eq!(
+            Strobe::new_with_rate(b"custom rate", SecParam::B128, rate).unwrap_err(),
+            StrobeError::InvalidRate
+        );
+    }
+}
+
+// bytes_processed should count every operation's data, and survive serialization
+#[test]
+fn test_bytes_processed() {
+    let mut s = Strobe::new(b"bytes_processed", SecParam::B128);
+    // The protocol string is absorbed at init
+    assert_eq!(s.bytes_processed(), 15);
+
+    s.key(b"0123456789", false);
+    s.ad(b"abc", false);
+    s.ad(b"de", true);
+    let mut buf = [0u8; 100];
+    s.send_enc(&mut buf, false);
+    s.meta_ratchet(32, false);
+    let mut mac = [0u8; 16];
+    s.send_mac(&mut mac, false);
+    assert_eq!(s.bytes_processed(), 15 + 10 + 3 + 2 + 100 + 32 + 16);
+
+    #[cfg(all(feature = "serialize_secret_state", feature = "alloc"))]
+    {
+        let restored = Strobe::from_bytes(&s.to_bytes()).unwrap();
+        assert_eq!(restored.bytes_processed(), s.bytes_processed());
+    }
+    #[cfg(all(feature = "serialize_secret_state", feature = "std"))]
+    {
+        let json = serde_json::to_vec(&s).unwrap();
+        let restored: Strobe = serde_json::from_slice(&json).unwrap();
+        assert_eq!(restored.bytes_processed(), s.bytes_processed());
+    }
+}
+
+// version_str_into should match version_str, and fail on too-small buffers
+#[cfg(feature = "heapless")]
+#[test]
+fn test_version_str_into() {
+    let s = Strobe::new(b"heapless", SecParam::B128);
+
+    let mut buf = heapless::String::<32>::new();
+    buf.push_str("junk").unwrap();
+    s.version_str_into(&mut buf).unwrap();
+    assert_eq!(buf.as_bytes(), &s.version_str()[..]);
+
+    let mut small = heapless::String::<28>::new();
+    assert_eq!(
+        s.version_str_into(&mut small),
+        Err(StrobeError::BufferTooSmall)
+    );
+}
+
+// version_str_into_slice should match version_str, and fail on too-small buffers
+#[test]
+fn test_version_str_into_slice() {
+    for sec in [SecParam::B128, SecParam::B256] {
+        let s = Strobe::new(b"version_str_into_slice", sec);
+
+        let mut buf = [0xffu8; 40];
+        let version_str = s.version_str_into_slice(&mut buf).unwrap();
+        assert_eq!(version_str.as_bytes(), &s.version_str()[..]);
+
+        let mut small = [0xffu8; 28];
+        assert_eq!(s.version_str_into_slice(&mut small), Err(core::fmt::Error));
+        assert_eq!(small, [0xff; 28]);
+    }
+}
+
+// Nonces should be deterministic per counter, distinct across counters, and not touch the state
+#[test]
+fn test_derive_nonce() {
+    let mut s = Strobe::new(b"derive_nonce", SecParam::B128);
+    s.key(b"key", false);
+    let before = s.transcript_tag();
+
+    let mut nonces = [[0u8; 12]; 4];
+    for (counter, nonce) in nonces.iter_mut().enumerate() {
+        s.derive_nonce(counter as u64, nonce);
+    }
+    for i in 0..nonces.len() {
+        for j in 0..i {
+            assert_ne!(nonces[i], nonces[j]);
+        }
+    }
+
+    let mut again = [0u8; 12];
+    s.derive_nonce(2, &mut again);
+    assert_eq!(again, nonces[2]);
+
+    assert_eq!(s.transcript_tag(), before);
+}
+
+// Reinitializing should be indistinguishable from making a new Strobe
+#[test]
+fn test_reinit() {
+    let mut s = Strobe::new(b"pooled", SecParam::B128);
+    s.key(b"key", false);
+    let mut buf = [0u8; 300];
+    s.send_enc(&mut buf, false);
+
+    for sec in [SecParam::B256, SecParam::B128] {
+        s.reinit(b"reinit", sec);
+        let fresh = Strobe::new(b"reinit", sec);
+
+        assert_eq!(s.version_str(), fresh.version_str());
+        assert_eq!(s.is_receiver(), None);
+        assert_eq!(s.transcript_tag(), fresh.transcript_tag());
+        #[cfg(feature = "std")]
+        assert_eq!(std::format!("{:?}", s), std::format!("{:?}", fresh));
+    }
+}
+
+// Contextual construction should disambiguate the split between base and context
+#[test]
+fn test_new_contextual() {
+    let s1 = Strobe::new_contextual(b"a", b"bc", SecParam::B128);
+    let s2 = Strobe::new_contextual(b"ab", b"c", SecParam::B128);
+    assert_ne!(s1.transcript_tag(), s2.transcript_tag());
+
+    // It should be new followed by framed meta_ad
+    let mut manual = Strobe::new(b"a", SecParam::B128);
+    manual.meta_ad(2u64.to_le_bytes(), false);
+    manual.meta_ad(b"bc", false);
+    assert_eq!(s1.transcript_tag(), manual.transcript_tag());
+
+    // And compose with the rest of the API
+    let mut tx = Strobe::new_contextual(b"base", b"session 1", SecParam::B256);
+    let mut rx = Strobe::new_contextual(b"base", b"session 1", SecParam::B256);
+    let mut msg = *b"hello";
+    tx.send_enc(&mut msg, false);
+    rx.recv_enc(&mut msg, false);
+    assert_eq!(&msg, b"hello");
+    let mut mac = [0u8; 16];
+    tx.send_mac(&mut mac, false);
+    assert!(rx.recv_mac(&mac).is_ok());
+}
+
+// Batch MAC verification should advance the state like sequential recv_macs, and fail if any MAC
+// is bad
+#[test]
+fn test_recv_macs() {
+    let mut tx = Strobe::new(b"recv_macs", SecParam::B128);
+    let rx = Strobe::new(b"recv_macs", SecParam::B128);
+
+    let mut macs = [[0u8; 16]; 4];
+    for mac in macs.iter_mut() {
+        tx.send_mac(mac, false);
+    }
+
+    let mut batch = rx.clone();
+    assert!(batch.recv_macs(&macs).is_ok());
+
+    let mut sequential = rx.clone();
+    for mac in &macs {
+        assert!(sequential.recv_mac(mac).is_ok());
+    }
+    assert_eq!(batch.transcript_tag(), sequential.transcript_tag());
+
+    // Corrupt any one of the MACs, and the whole batch fails, with the state still advanced
+    // through every MAC
+    for i in 0..macs.len() {
+        let mut bad_macs = macs;
+        bad_macs[i][0] ^= 1;
+        let mut batch = rx.clone();
+        assert_eq!(batch.recv_macs(&bad_macs), Err(AuthError));
+        assert_eq!(batch.bytes_processed(), sequential.bytes_processed());
+    }
+
+    // Zero-length MACs fail, and an empty batch succeeds
+    assert_eq!(rx.clone().recv_macs(&[[0u8; 0]; 2]), Err(AuthError));
+    assert!(rx.clone().recv_macs::<16>(&[]).is_ok());
+}
+
+// Conditional assignment should copy everything or nothing, and refuse mismatched parameters
+#[cfg(feature = "subtle")]
+#[test]
+fn test_conditional_assign() {
+    use subtle::Choice;
+
+    let mut a = Strobe::new(b"conditional_assign", SecParam::B128);
+    let mut b = a.clone();
+    a.ad(b"some data", false);
+    let mut msg = [0u8; 7];
+    b.send_enc(&mut msg, false);
+    b.ad(b"streamed", false);
+
+    let orig_a = a.clone();
+    a.conditional_assign(&b, Choice::from(0)).unwrap();
+    assert_eq!(a.transcript_tag(), orig_a.transcript_tag());
+    assert_eq!(a.is_receiver(), None);
+
+    a.conditional_assign(&b, Choice::from(1)).unwrap();
+    assert_eq!(a.transcript_tag(), b.transcript_tag());
+    assert_eq!(a.is_receiver(), Some(false));
+    assert_eq!(a.bytes_processed(), b.bytes_processed());
+    // prev_flags was copied too, so a can continue b's streaming op
+    a.ad(b" more", true);
+
+    let c = Strobe::new(b"conditional_assign", SecParam::B256);
+    assert_eq!(
+        a.conditional_assign(&c, Choice::from(1)),
+        Err(StrobeError::SecParamMismatch)
+    );
+}
+
+// prf_uninit should give the same output and state as prf
+#[test]
+fn test_prf_uninit() {
+    use core::mem::MaybeUninit;
+
+    for len in [0, 1, 100, 166, 500] {
+        let mut s1 = Strobe::new(b"prf_uninit", SecParam::B128);
+        let mut s2 = s1.clone();
+        s1.ad(b"data", false);
+        s2.ad(b"data", false);
+
+        let mut expected = [0u8; 500];
+        s1.prf(&mut expected[..len], false);
+
+        let mut buf = [MaybeUninit::<u8>::uninit(); 500];
+        let out = s2.prf_uninit(&mut buf[..len]);
+        assert_eq!(out, &expected[..len]);
+        assert_eq!(s1.transcript_tag(), s2.transcript_tag());
+    }
+}
+
+// ad_many should be the same as a one-shot ad of the concatenation
+#[test]
+fn test_ad_many() {
+    let chunks: [&[u8]; 5] = [b"a", b"", b"bcd", &[0x42; 300], b"e"];
+    let mut concat = [0u8; 305];
+    let mut i = 0;
+    for chunk in chunks {
+        concat[i..i + chunk.len()].copy_from_slice(chunk);
+        i += chunk.len();
+    }
+
+    let mut s1 = Strobe::new(b"ad_many", SecParam::B128);
+    let mut s2 = s1.clone();
+    s1.ad_many(chunks.iter().copied());
+    s2.ad(concat, false);
+    assert_eq!(s1.transcript_tag(), s2.transcript_tag());
+
+    // Empty input
+    let mut s1 = Strobe::new(b"ad_many", SecParam::B128);
+    let mut s2 = s1.clone();
+    s1.ad_many(core::iter::empty());
+    s2.ad([], false);
+    assert_eq!(s1.transcript_tag(), s2.transcript_tag());
+}
+
+// Errors should have useful messages
+#[cfg(feature = "std")]
+#[test]
+fn test_error_display() {
+    use std::string::ToString;
+
+    assert_eq!(AuthError.to_string(), "STROBE MAC verification failed");
+    assert_eq!(
+        StrobeError::from(AuthError).to_string(),
+        "MAC verification failed"
+    );
+}
+
+// Framing a length given as a u64 should match framing a slice, and shouldn't depend on usize
+#[test]
+fn test_begin_ad_framed() {
+    let data = [0x17u8; 300];
+
+    let mut s1 = Strobe::new(b"begin_ad_framed", SecParam::B128);
+    let mut s2 = s1.clone();
+    s1.ad_framed(&data);
+    s2.begin_ad_framed(data.len() as u64);
+    s2.ad(&data[..100], false);
+    s2.ad(&data[100..], true);
+    assert_eq!(s1.transcript_tag(), s2.transcript_tag());
+
+    // A 5GiB length doesn't fit in a 32-bit usize. Whatever the pointer width, the prefix is the
+    // 8-byte little-endian encoding.
+    let big_len: u64 = 5 << 30;
+    let mut s1 = Strobe::new(b"begin_ad_framed", SecParam::B128);
+    let mut s2 = s1.clone();
+    s1.begin_ad_framed(big_len);
+    s2.meta_ad([0, 0, 0, 0x40, 0x01, 0, 0, 0], false);
+    assert_eq!(s1.transcript_tag(), s2.transcript_tag());
+}
+
+// The flag table should cover every KAT op name, and agree with the typed methods
+#[test]
+fn test_flags_for() {
+    use crate::strobe::{flags_for, AD_FLAGS, PRF_FLAGS, RATCHET_FLAGS, SEND_ENC_FLAGS};
+
+    assert_eq!(flags_for("AD"), Some(AD_FLAGS));
+    assert_eq!(flags_for("PRF"), Some(OpFlags::I | OpFlags::A | OpFlags::C));
+    assert_eq!(flags_for("RATCHET"), Some(RATCHET_FLAGS));
+    assert_eq!(flags_for("ENC"), None);
+
+    // Running the raw op with the table's flags should match the typed method
+    let mut s1 = Strobe::new(b"flags_for", SecParam::B128);
+    let mut s2 = s1.clone();
+    let mut buf1 = *b"some plaintext";
+    let mut buf2 = buf1;
+    s1.send_enc(&mut buf1, false);
+    s2.operate(SEND_ENC_FLAGS, &mut buf2, false);
+    assert_eq!(buf1, buf2);
+
+    let mut out1 = [0u8; 16];
+    let mut out2 = [0u8; 16];
+    s1.meta_prf(&mut out1, false);
+    s2.operate(PRF_FLAGS | OpFlags::M, &mut out2, false);
+    assert_eq!(out1, out2);
+}
+
+// The owned-buffer ops should round-trip, and match the in-place ops
+#[test]
+fn test_enc_owned() {
+    let mut tx = Strobe::new(b"enc_owned", SecParam::B128);
+    let mut rx = tx.clone();
+    let mut reference = tx.clone();
+
+    let ct = tx.send_enc_owned(*b"owned message");
+    let mut expected = *b"owned message";
+    reference.send_enc(&mut expected, false);
+    assert_eq!(ct, expected);
+
+    assert_eq!(&rx.recv_enc_owned(ct), b"owned message");
+}
+
+// Security tags should only depend on the configuration
+#[test]
+fn test_security_tag() {
+    let mut a128 = Strobe::new(b"security_tag", SecParam::B128);
+    let b128 = Strobe::new(b"another proto", SecParam::B128);
+    let a256 = Strobe::new(b"security_tag", SecParam::B256);
+    let custom_rate = Strobe::new_with_rate(b"security_tag", SecParam::B128, 100).unwrap();
+
+    let tag = a128.security_tag();
+    assert_eq!(tag, b128.security_tag());
+    assert_ne!(tag, a256.security_tag());
+    assert_ne!(tag, custom_rate.security_tag());
+
+    a128.key(b"key", false);
+    assert_eq!(a128.security_tag(), tag);
+}
+
+// A MAC can't be verified in pieces. Each recv_mac call is a separate operation, so verifying the
+// second half of a MAC on its own fails.
+#[test]
+fn test_split_mac_fails() {
+    let mut tx = Strobe::new(b"split mac", SecParam::B128);
+    let mut rx = tx.clone();
+
+    let mut mac = [0u8; 32];
+    tx.send_mac(&mut mac, false);
+
+    let mut whole = rx.clone();
+    assert!(whole.recv_mac(&mac).is_ok());
+
+    let (first, second) = mac.split_at(16);
+    let _ = rx.recv_mac::<16>(first.try_into().unwrap());
+    assert_eq!(
+        rx.recv_mac::<16>(second.try_into().unwrap()),
+        Err(AuthError)
+    );
+}
+
+// output_len should match what each typed operation actually writes
+#[test]
+fn test_output_len() {
+    use crate::strobe::flags_for;
+
+    let with_output = ["PRF", "send_ENC", "recv_ENC", "send_MAC"];
+    let without_output = ["AD", "KEY", "send_CLR", "recv_CLR", "recv_MAC", "RATCHET"];
+    for name in with_output {
+        let flags = flags_for(name).unwrap();
+        assert_eq!(Strobe::output_len(flags, 10), 10, "{}", name);
+        assert_eq!(
+            Strobe::output_len(flags | OpFlags::M, 10),
+            10,
+            "meta {}",
+            name
+        );
+    }
+    for name in without_output {
+        let flags = flags_for(name).unwrap();
+        assert_eq!(Strobe::output_len(flags, 10), 0, "{}", name);
+        assert_eq!(
+            Strobe::output_len(flags | OpFlags::M, 10),
+            0,
+            "meta {}",
+            name
+        );
+    }
+}
+
+// Labels that are prefixes of each other must give distinct outputs
+#[test]
+fn test_labeled_prf_prefix() {
+    let s = Strobe::new(b"labeled_prf test", SecParam::B256);
+
+    let out_a: [u8; 32] = s.clone().labeled_prf_array(b"a");
+    let out_ab: [u8; 32] = s.clone().labeled_prf_array(b"ab");
+    assert_ne!(out_a, out_ab);
+
+    // The array variant should agree with the slice variant
+    let mut out = [0u8; 32];
+    s.clone().labeled_prf(b"a", &mut out);
+    assert_eq!(out, out_a);
+}
+
+/*
+A known-answer test for labeled_prf, which pins down separator's framing. The expected output is
+that of these STROBE operations, computed independently of this crate:
+s = Strobe("labeled_prf kat", security=256)
+s.meta_ad((9).to_bytes(8, "little"))
+s.meta_ad("handshake")
+s.prf(32)
+*/
+#[test]
+fn test_labeled_prf_kat() {
+    let mut s = Strobe::new(b"labeled_prf kat", SecParam::B256);
+    let out: [u8; 32] = s.labeled_prf_array(b"handshake");
+    let expected: [u8; 32] = [
+        0x6a, 0xb2, 0xe0, 0x18, 0xd3, 0x66, 0x3f, 0x51, 0xc8, 0x8b, 0xbc, 0x30, 0x6c, 0x23, 0x98,
+        0x7d, 0x12, 0x72, 0x4c, 0x66, 0x7b, 0x93, 0x5d, 0xfe, 0x93, 0x41, 0x51, 0x1f, 0xf8, 0xf6,
+        0x8f, 0xaa,
+    ];
+    assert_eq!(out, expected);
+}
+
+// The no-mutate operations accept anything that's AsRef<[u8]>, and treat it like the equivalent
+// slice
+#[test]
+fn test_as_ref_inputs() {
+    let mut s1 = Strobe::new(b"as_ref test", SecParam::B256);
+    let mut s2 = s1.clone();
+
+    s1.ad(&b"hello"[..], false);
+    s1.meta_key(&[1u8, 2, 3][..], false);
+    s1.send_clr(&b"world"[..], false);
+    s2.ad("hello", false);
+    s2.meta_key([1u8, 2, 3], false);
+    #[cfg(feature = "alloc")]
+    s2.send_clr(alloc::vec::Vec::from(&b"world"[..]), false);
+    #[cfg(not(feature = "alloc"))]
+    s2.send_clr(b"world", false);
+
+    assert_eq!(s1.transcript_tag(), s2.transcript_tag());
+}
+
+// Snapshots should reflect exactly the data absorbed so far, and not disturb later absorption
+#[test]
+fn test_digest_snapshot() {
+    let mut s = Strobe::new(b"digest_snapshot test", SecParam::B256);
+    let mut untouched = s.clone();
+
+    s.ad(b"entry 1", false);
+    untouched.ad(b"entry 1", false);
+    let mut snap1 = [0u8; 32];
+    s.digest_snapshot(&mut snap1);
+
+    s.ad(b"entry 2", true);
+    untouched.ad(b"entry 2", true);
+    let mut snap2 = [0u8; 32];
+    s.digest_snapshot(&mut snap2);
+    assert_ne!(snap1, snap2);
+
+    // A snapshot is a labeled prf on a copy of the state
+    let mut expected = [0u8; 32];
+    let mut copy = untouched.clone();
+    copy.meta_ad(b"digest snapshot", false);
+    copy.prf(&mut expected, false);
+    assert_eq!(snap2, expected);
+
+    // The snapshots didn't change the ongoing session
+    assert_eq!(s.transcript_tag(), untouched.transcript_tag());
+
+    // And a snapshot doesn't reveal the session's next prf output
+    let mut next_prf = [0u8; 32];
+    s.prf(&mut next_prf, false);
+    assert_ne!(snap2, next_prf);
+}
+
+// Keying from shares should be the same as keying from their concatenation, however the key is
+// split
+#[test]
+fn test_key_shares() {
+    use rand::Rng;
+
+    let mut rng = rand::thread_rng();
+    let mut key = [0u8; 100];
+    rng.fill(&mut key[..]);
+
+    let base = Strobe::new(b"key_shares test", SecParam::B256);
+    let mut whole = base.clone();
+    whole.key(key, false);
+
+    for _ in 0..50 {
+        // Pick a random set of split points, possibly with empty shares
+        let mut splits = [0usize; 4];
+        for split in splits.iter_mut() {
+            *split = rng.gen_range(0..=key.len());
+        }
+        splits.sort_unstable();
+
+        let shares = [
+            &key[..splits[0]],
+            &key[splits[0]..splits[1]],
+            &key[splits[1]..splits[2]],
+            &key[splits[2]..splits[3]],
+            &key[splits[3]..],
+        ];
+        let mut shared = base.clone();
+        shared.key_shares(&shares);
+        assert_eq!(shared.st.0, whole.st.0, "splits {:?}", splits);
+    }
+
+    // No shares is an empty key
+    let mut s1 = base.clone();
+    let mut s2 = base;
+    s1.key_shares(&[]);
+    s2.key([], false);
+    assert_eq!(s1.st.0, s2.st.0);
+}
+
+// is_keyed should be set by key and meta_key, survive serialization, and gate send_enc_checked
+#[test]
+fn test_is_keyed() {
+    let mut s = Strobe::new(b"is_keyed test", SecParam::B256);
+    s.ad(b"not a key", false);
+    assert!(!s.is_keyed());
+
+    // Encrypting under an unkeyed state is refused, and nothing changes
+    let mut msg = *b"attack at dawn";
+    let before = s.clone();
+    assert_eq!(
+        s.send_enc_checked(&mut msg, false),
+        Err(StrobeError::Unkeyed)
+    );
+    assert_eq!(&msg, b"attack at dawn");
+    assert_eq!(s.transcript_tag(), before.transcript_tag());
+
+    let mut meta_keyed = s.clone();
+    meta_keyed.meta_key(b"meta key", false);
+    assert!(meta_keyed.is_keyed());
+
+    s.key(b"the key", false);
+    assert!(s.is_keyed());
+    let mut expected = s.clone();
+    let mut expected_ct = msg;
+    expected.send_enc(&mut expected_ct, false);
+    s.send_enc_checked(&mut msg, false).unwrap();
+    assert_eq!(msg, expected_ct);
+
+    #[cfg(all(feature = "serialize_secret_state", feature = "alloc"))]
+    {
+        assert!(Strobe::from_bytes(&s.to_bytes()).unwrap().is_keyed());
+        assert!(!Strobe::from_bytes(&before.to_bytes()).unwrap().is_keyed());
+    }
+    #[cfg(all(feature = "serialize_secret_state", feature = "std"))]
+    {
+        let json = serde_json::to_vec(&s).unwrap();
+        let restored: Strobe = serde_json::from_slice(&json).unwrap();
+        assert!(restored.is_keyed());
+    }
+}
+
+// Fixed-size inputs, byte-string literals, and GenericArrays all work directly as op inputs
+// The borrows are deliberate, since that's how most existing call sites look
+#[allow(clippy::needless_borrows_for_generic_args)]
+#[test]
+fn test_fixed_size_inputs() {
+    let mut s1 = Strobe::new(b"fixed-size input test", SecParam::B256);
+    let mut s2 = s1.clone();
+
+    let arr = [1u8, 2, 3];
+    s1.ad(&arr[..], false);
+    s1.key(&b"key"[..], false);
+    s2.ad(&[1, 2, 3], false);
+    s2.key(b"key", false);
+    assert_eq!(s1.transcript_tag(), s2.transcript_tag());
+
+    s1.ad(&arr[..], false);
+    s2.ad(arr, false);
+    assert_eq!(s1.transcript_tag(), s2.transcript_tag());
+
+    #[cfg(feature = "generic-array")]
+    {
+        let ga = generic_array::GenericArray::<u8, generic_array::typenum::U3>::from(arr);
+        s1.meta_ad(&arr[..], false);
+        s2.meta_ad(ga, false);
+        assert_eq!(s1.transcript_tag(), s2.transcript_tag());
+    }
+}
+
+// try_new should agree with new on valid parameters, and fail instead of panicking on invalid ones
+#[test]
+fn test_try_new() {
+    for sec in [SecParam::B128, SecParam::B256] {
+        let s1 = Strobe::new(b"try_new test", sec);
+        let s2 = Strobe::try_new(b"try_new test", sec).unwrap();
+        assert_eq!(s1.st.0, s2.st.0);
+    }
+
+    for bits in [0, 130, 792] {
+        assert_eq!(
+            Strobe::new_with_security(b"try_new test", bits).unwrap_err(),
+            StrobeError::InvalidSecurityParam
+        );
+    }
+}
+
+// A separator is a length-framed meta_ad, done as a single operation
+#[test]
+fn test_separator() {
+    let base = Strobe::new(b"separator test", SecParam::B256);
+
+    let mut s1 = base.clone();
+    let mut s2 = base.clone();
+    s1.separator(b"handshake");
+    s2.meta_ad([9, 0, 0, 0, 0, 0, 0, 0], false);
+    s2.meta_ad(b"handshake", false);
+    assert_eq!(s1.st.0, s2.st.0);
+
+    // It uses the same framing as new_contextual
+    let contextual = Strobe::new_contextual(b"separator test", b"handshake", SecParam::B256);
+    assert_eq!(s1.st.0, contextual.st.0);
+
+    // Adjacent separators can't be merged
+    let mut s3 = base.clone();
+    let mut s4 = base;
+    s3.separator(b"a");
+    s3.separator(b"b");
+    s4.separator(b"ab");
+    assert_ne!(s3.transcript_tag(), s4.transcript_tag());
+}
+
+// The permutation count should track every run of Keccak-f after initialization
+#[test]
+fn test_permutation_count() {
+    let mut s = Strobe::new(b"perm count", SecParam::B256);
+    assert_eq!(s.permutation_count(), 0);
+
+    // Cipher ops always start on a fresh block
+    s.key(b"k", false);
+    assert_eq!(s.permutation_count(), 1);
+
+    // After the key, pos is 1. The ad's begin_op absorbs 2 bytes, and then 3 * 134 bytes of data
+    // cross the rate boundary 3 times.
+    s.ad([0u8; 3 * 134], false);
+    assert_eq!(s.permutation_count(), 4);
+
+    // The count isn't serialized, and isn't part of equality
+    #[cfg(all(feature = "serialize_secret_state", feature = "alloc"))]
+    {
+        let restored = Strobe::from_bytes(&s.to_bytes()).unwrap();
+        assert_eq!(restored.permutation_count(), 0);
+        #[cfg(feature = "test-util")]
+        assert!(restored == s);
+    }
+}
+
+// recv_enc_exact should decrypt like recv_enc, and leave everything alone on a length mismatch
+#[test]
+fn test_recv_enc_exact() {
+    let mut tx = Strobe::new(b"recv_enc_exact test", SecParam::B256);
+    tx.key(b"the key", false);
+    let mut rx = tx.clone();
+
+    let mut msg = *b"ten bytes!";
+    tx.send_enc(&mut msg, false);
+    let ct = msg;
+
+    let before = rx.clone();
+    assert_eq!(
+        rx.recv_enc_exact(&mut msg, 9, false),
+        Err(StrobeError::LengthMismatch)
+    );
+    assert_eq!(
+        rx.recv_enc_exact(&mut msg, 11, false),
+        Err(StrobeError::LengthMismatch)
+    );
+    assert_eq!(msg, ct);
+    assert_eq!(rx.transcript_tag(), before.transcript_tag());
+
+    rx.recv_enc_exact(&mut msg, 10, false).unwrap();
+    assert_eq!(&msg, b"ten bytes!");
+    assert_eq!(rx.transcript_tag(), tx.transcript_tag());
+}
+
+// secret_clone is the same as clone, and the copies evolve independently
+#[test]
+fn test_secret_clone() {
+    let mut s = Strobe::new(b"secret_clone test", SecParam::B256);
+    s.key(b"the key", false);
+
+    let mut copy = s.secret_clone();
+    assert_eq!(copy.transcript_tag(), s.clone().transcript_tag());
+
+    copy.ad(b"only in the copy", false);
+    assert_ne!(copy.transcript_tag(), s.transcript_tag());
+}
+
+// Binding to different transcripts should give different states, and leave the child alone
+#[test]
+fn test_bind() {
+    let parent = Strobe::new(b"bind parent", SecParam::B256);
+    let mut child1 = Strobe::new(b"bind child", SecParam::B256);
+    let mut child2 = child1.clone();
+    child1.ad(b"one history", false);
+    child2.ad(b"another history", false);
+
+    let child1_tag = child1.transcript_tag();
+    let mut p1 = parent.clone();
+    let mut p2 = parent;
+    p1.bind(&child1);
+    p2.bind(&child2);
+    assert_ne!(p1.transcript_tag(), p2.transcript_tag());
+    assert_eq!(child1.transcript_tag(), child1_tag);
+}
+
+// ad_iter should give the same state as ad on the collected bytes, for any length
+#[test]
+fn test_ad_iter() {
+    let base = Strobe::new(b"ad_iter test", SecParam::B128);
+    let data: [u8; 300] = {
+        let mut data = [0u8; 300];
+        for (i, b) in data.iter_mut().enumerate() {
+            *b = (i * 7) as u8;
+        }
+        data
+    };
+
+    for len in [0, 1, 63, 64, 65, 128, 300] {
+        let mut s1 = base.clone();
+        let mut s2 = base.clone();
+        s1.ad_iter(data[..len].iter().copied(), false);
+        s2.ad(&data[..len], false);
+        assert_eq!(s1.st.0, s2.st.0, "len {}", len);
+
+        // Continuing an existing op works too
+        s1.ad_iter(data[..len].iter().copied(), true);
+        s2.ad(&data[..len], true);
+        assert_eq!(s1.st.0, s2.st.0, "len {}", len);
+    }
+}
+
+// recv_mac_len should verify like recv_mac, and treat a wrong length like a wrong tag
+#[test]
+fn test_recv_mac_len() {
+    let mut tx = Strobe::new(b"recv_mac_len test", SecParam::B256);
+    tx.key(b"the key", false);
+    let rx = tx.clone();
+
+    let mut mac = [0u8; 100];
+    tx.send_mac(&mut mac, false);
+
+    // A correct tag verifies, and the state matches recv_mac's, even across chunks
+    let mut rx1 = rx.clone();
+    let mut rx2 = rx.clone();
+    assert!(rx1.recv_mac_len(&mac, 100).is_ok());
+    assert!(rx2.recv_mac(&mac).is_ok());
+    assert_eq!(rx1.st.0, rx2.st.0);
+
+    // A truncated tag fails, and the state is as if it were padded with zeros
+    let mut padded = [0u8; 100];
+    padded[..50].copy_from_slice(&mac[..50]);
+    let mut rx1 = rx.clone();
+    let mut rx2 = rx.clone();
+    assert_eq!(rx1.recv_mac_len(&mac[..50], 100), Err(AuthError));
+    assert_eq!(rx2.recv_mac(&padded), Err(AuthError));
+    assert_eq!(rx1.st.0, rx2.st.0);
+
+    // A tag that's too long fails, and only expected_len bytes of it are processed
+    let mut long = [0u8; 101];
+    long[..100].copy_from_slice(&mac);
+    let mut rx1 = rx.clone();
+    let mut rx2 = rx.clone();
+    assert_eq!(rx1.recv_mac_len(&long, 100), Err(AuthError));
+    assert!(rx2.recv_mac(&mac).is_ok());
+    assert_eq!(rx1.st.0, rx2.st.0);
+
+    // An empty tag never verifies
+    let mut rx1 = rx;
+    assert_eq!(rx1.recv_mac_len(&[], 0), Err(AuthError));
+}
+
+// Processing exactly rate_remaining bytes should trigger exactly one permutation
+#[test]
+fn test_rate_remaining() {
+    let mut s = Strobe::new(b"rate_remaining test", SecParam::B128);
+    s.ad(b"begin", false);
+
+    let remaining = s.rate_remaining();
+    assert!(remaining > 0 && remaining <= 166);
+
+    let before = s.permutation_count();
+    s.ad(&[0u8; 166][..remaining - 1], true);
+    assert_eq!(s.permutation_count(), before);
+    assert_eq!(s.rate_remaining(), 1);
+    s.ad([0u8], true);
+    assert_eq!(s.permutation_count(), before + 1);
+    assert_eq!(s.rate_remaining(), 166);
+}
+
+// bind_version should be a meta_ad of the version string, so different versions diverge
+#[test]
+fn test_bind_version() {
+    let base = Strobe::new(b"bind_version test", SecParam::B256);
+
+    let mut s1 = base.clone();
+    s1.bind_version();
+    let mut s2 = base.clone();
+    s2.meta_ad(b"Strobe-Keccak-256/1600-v1.0.2", false);
+    assert_eq!(s1.transcript_tag(), s2.transcript_tag());
+
+    // Simulate a peer on a different version
+    let mut s3 = base;
+    s3.meta_ad(b"Strobe-Keccak-256/1600-v1.0.3", false);
+    assert_ne!(s1.transcript_tag(), s3.transcript_tag());
+}
+
+// encrypt/decrypt should round-trip, and reject tampering and short inputs
+#[cfg(feature = "alloc")]
+#[test]
+fn test_encrypt_decrypt() {
+    let base = Strobe::new(b"encrypt test", SecParam::B256);
+    let (key, nonce) = (b"the key", b"nonce 1");
+    let msg = b"attack at dawn";
+
+    let ct = base.clone().encrypt(key, nonce, msg);
+    assert_eq!(ct.len(), msg.len() + 16);
+    assert_eq!(base.clone().decrypt(key, nonce, &ct).unwrap(), msg.to_vec());
+
+    // Wrong key, wrong nonce, or a modified ciphertext all fail
+    assert!(base.clone().decrypt(b"other key", nonce, &ct).is_err());
+    assert!(base.clone().decrypt(key, b"nonce 2", &ct).is_err());
+    let mut bad = ct.clone();
+    bad[0] ^= 1;
+    assert_eq!(
+        base.clone().decrypt(key, nonce, &bad),
+        Err(StrobeError::MacMismatch)
+    );
+
+    // Too short to hold a MAC, and the state is untouched
+    let mut s = base.clone();
+    assert_eq!(
+        s.decrypt(key, nonce, &ct[..15]),
+        Err(StrobeError::MacMismatch)
+    );
+    assert_eq!(s.transcript_tag(), base.transcript_tag());
+}
+
+// ad_ioslices should be the same as ad over the concatenation
+#[cfg(feature = "std")]
+#[test]
+fn test_ad_ioslices() {
+    use std::io::IoSlice;
+
+    let base = Strobe::new(b"ad_ioslices test", SecParam::B256);
+    let data = [0x5au8; 400];
+
+    let mut s1 = base.clone();
+    let mut s2 = base.clone();
+    let slices = [
+        IoSlice::new(&data[..10]),
+        IoSlice::new(&[]),
+        IoSlice::new(&data[10..300]),
+        IoSlice::new(&data[300..]),
+    ];
+    s1.ad_ioslices(&slices, false);
+    s2.ad(data, false);
+    assert_eq!(s1.st.0, s2.st.0);
+
+    // Continuing an op, and the empty case
+    s1.ad_ioslices(&slices[..2], true);
+    s2.ad(&data[..10], true);
+    assert_eq!(s1.st.0, s2.st.0);
+    let mut s3 = base.clone();
+    let mut s4 = base;
+    s3.ad_ioslices(&[], false);
+    s4.ad([], false);
+    assert_eq!(s3.st.0, s4.st.0);
+}
+
+// The blocks from prf_for_each should concatenate to a one-shot prf
+#[cfg(feature = "alloc")]
+#[test]
+fn test_prf_for_each() {
+    let mut base = Strobe::new(b"prf_for_each test", SecParam::B128);
+    base.key(b"the key", false);
+
+    for total in [0, 1, 165, 166, 167, 1000] {
+        let mut s1 = base.clone();
+        let mut s2 = base.clone();
+
+        let mut streamed = alloc::vec::Vec::new();
+        let mut calls = 0;
+        s1.prf_for_each(total, |block| {
+            assert!(!block.is_empty() && block.len() <= 166);
+            streamed.extend_from_slice(block);
+            calls += 1;
+        });
+
+        let mut one_shot = alloc::vec![0u8; total];
+        s2.prf(&mut one_shot, false);
+        assert_eq!(streamed, one_shot, "total {}", total);
+        assert_eq!(s1.st.0, s2.st.0, "total {}", total);
+        assert_eq!(calls, (total + 165) / 166, "total {}", total);
+    }
+}
+
+// Checkpoints should match between an original run and a faithful replay, and diverge otherwise
+#[test]
+fn test_checkpoint() {
+    let base = Strobe::new(b"checkpoint test", SecParam::B256);
+    let ops = |s: &mut Strobe, msg: &[u8]| {
+        s.key(b"the key", false);
+        s.send_clr(msg, false);
+        let mut mac = [0u8; 16];
+        s.send_mac(&mut mac, false);
+    };
+
+    let mut original = base.clone();
+    ops(&mut original, b"hello");
+    let saved = original.checkpoint();
+
+    // Taking a checkpoint doesn't change anything
+    assert_eq!(original.checkpoint(), saved);
+    assert_eq!(saved, original.transcript_tag());
+
+    let mut replay = base.clone();
+    ops(&mut replay, b"hello");
+    assert_eq!(replay.checkpoint(), saved);
+
+    let mut bad_replay = base;
+    ops(&mut bad_replay, b"hellO");
+    assert_ne!(bad_replay.checkpoint(), saved);
+}
@@ -0,0 +1,102 @@
+use crate::strobe::Strobe;
+
+/// A fluent wrapper around a `&mut Strobe`, returned by [`Strobe::chain`]. Each method runs the
+/// `Strobe` method of the same name with `more = false`, and returns the wrapper, so that a
+/// transcript can be written as a single expression:
+///
+/// ```
+/// # use strobe_rs::{SecParam, Strobe};
+/// let mut s = Strobe::new(b"chain example", SecParam::B128);
+/// let mut msg = *b"hello";
+/// s.chain()
+///     .ad(b"header")
+///     .key(b"secret key")
+///     .send_clr(b"cleartext")
+///     .send_enc(&mut msg);
+/// ```
+///
+/// The state transitions are exactly those of the underlying calls. Mutating operations modify
+/// their buffer in place.
+#[derive(Debug)]
+pub struct Chain<'a> {
+    strobe: &'a mut Strobe,
+}
+
+// This defines chaining versions of an operation and its meta-operation, with the given input type
+macro_rules! def_chain_op {
+    ($name:ident, $meta_name:ident, $input:ty) => {
+        #[doc = concat!("Runs [`Strobe::", stringify!($name), "`] with `more = false`.")]
+        pub fn $name(self, data: $input) -> Chain<'a> {
+            self.strobe.$name(data, /* more */ false);
+            self
+        }
+
+        #[doc = concat!("Runs [`Strobe::", stringify!($meta_name), "`] with `more = false`.")]
+        pub fn $meta_name(self, data: $input) -> Chain<'a> {
+            self.strobe.$meta_name(data, /* more */ false);
+            self
+        }
+    };
+}
+
+impl<'a> Chain<'a> {
+    pub(crate) fn new(strobe: &'a mut Strobe) -> Chain<'a> {
+        Chain { strobe }
+    }
+
+    def_chain_op!(ad, meta_ad, impl AsRef<[u8]>);
+    def_chain_op!(key, meta_key, impl AsRef<[u8]>);
+    def_chain_op!(send_clr, meta_send_clr, impl AsRef<[u8]>);
+    def_chain_op!(recv_clr, meta_recv_clr, impl AsRef<[u8]>);
+    def_chain_op!(send_enc, meta_send_enc, &mut [u8]);
+    def_chain_op!(recv_enc, meta_recv_enc, &mut [u8]);
+    def_chain_op!(send_mac, meta_send_mac, &mut [u8]);
+    def_chain_op!(prf, meta_prf, &mut [u8]);
+
+    /// Runs [`Strobe::ratchet`] with `more = false`
+    pub fn ratchet(self, num_bytes_to_zero: usize) -> Chain<'a> {
+        self.strobe.ratchet(num_bytes_to_zero, /* more */ false);
+        self
+    }
+
+    /// Runs [`Strobe::meta_ratchet`] with `more = false`
+    pub fn meta_ratchet(self, num_bytes_to_zero: usize) -> Chain<'a> {
+        self.strobe
+            .meta_ratchet(num_bytes_to_zero, /* more */ false);
+        self
+    }
+}
+
+#[test]
+fn chain_matches_calls() {
+    use crate::strobe::SecParam;
+
+    let mut s1 = Strobe::new(b"chain", SecParam::B128);
+    let mut s2 = s1.clone();
+
+    let mut msg1 = *b"hello";
+    let mut msg2 = msg1;
+    let mut mac1 = [0u8; 16];
+    let mut mac2 = [0u8; 16];
+
+    s1.chain()
+        .ad(b"a")
+        .ad([1u8, 2, 3])
+        .meta_key(b"k")
+        .send_clr(b"m")
+        .send_enc(&mut msg1)
+        .ratchet(32)
+        .send_mac(&mut mac1);
+
+    s2.ad(b"a", false);
+    s2.ad([1u8, 2, 3], false);
+    s2.meta_key(b"k", false);
+    s2.send_clr(b"m", false);
+    s2.send_enc(&mut msg2, false);
+    s2.ratchet(32, false);
+    s2.send_mac(&mut mac2, false);
+
+    assert_eq!(msg1, msg2);
+    assert_eq!(mac1, mac2);
+    assert_eq!(s1.transcript_tag(), s2.transcript_tag());
+}
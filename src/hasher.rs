@@ -0,0 +1,131 @@
+// A keyed `core::hash::Hasher` backed by `Strobe`, in the spirit of SipHash
+
+use crate::strobe::{SecParam, Strobe};
+
+use core::hash::{BuildHasher, Hasher};
+
+/// A keyed hasher that implements `core::hash::Hasher`. Everything written to it is absorbed as a
+/// single streaming `ad` operation, and `finish` returns the 8-byte `prf` of a copy of the state,
+/// read as a little-endian `u64`. The state is `Strobe::new(b"strobe-rs hasher", SecParam::B128)`
+/// followed by `key(seed, false)`.
+///
+/// When the seed is secret and random, the output is unpredictable to an attacker, so this is
+/// suitable for hash tables exposed to untrusted input. Use [`StrobeBuildHasher`] to plug it into
+/// a `HashMap`. With a public seed, this is a deterministic hash, with no resistance to
+/// hash-flooding.
+#[derive(Clone, Debug)]
+pub struct StrobeHasher {
+    strobe: Strobe,
+    // Whether anything has been written, i.e., whether the next write continues the ad
+    more: bool,
+}
+
+impl StrobeHasher {
+    /// Makes a new `StrobeHasher` keyed with `seed`
+    pub fn new(seed: &[u8]) -> StrobeHasher {
+        let mut strobe = Strobe::new(b"strobe-rs hasher", SecParam::B128);
+        strobe.key(seed, /* more */ false);
+        StrobeHasher {
+            strobe,
+            more: false,
+        }
+    }
+}
+
+impl Hasher for StrobeHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.strobe.ad(bytes, self.more);
+        self.more = true;
+    }
+
+    fn finish(&self) -> u64 {
+        self.strobe.clone().prf_u64()
+    }
+}
+
+/// A `BuildHasher` that makes [`StrobeHasher`]s, all keyed with the same seed. This is for use as
+/// the hasher of a `HashMap` or `HashSet`. For resistance to hash-flooding, the seed must be
+/// secret and random.
+#[derive(Clone, Debug)]
+pub struct StrobeBuildHasher {
+    hasher: StrobeHasher,
+}
+
+impl StrobeBuildHasher {
+    /// Makes a new `StrobeBuildHasher` whose hashers are keyed with `seed`
+    pub fn new(seed: &[u8]) -> StrobeBuildHasher {
+        StrobeBuildHasher {
+            hasher: StrobeHasher::new(seed),
+        }
+    }
+}
+
+impl BuildHasher for StrobeBuildHasher {
+    type Hasher = StrobeHasher;
+
+    fn build_hasher(&self) -> StrobeHasher {
+        // Keying is the expensive part, so do it once and clone the result
+        self.hasher.clone()
+    }
+}
+
+#[cfg(test)]
+use core::hash::Hash;
+
+#[cfg(test)]
+#[derive(Hash)]
+struct Record<'a> {
+    id: u64,
+    name: &'a str,
+    tags: [u8; 3],
+}
+
+#[cfg(test)]
+fn hash_with_seed<T: Hash>(seed: &[u8], value: &T) -> u64 {
+    let mut hasher = StrobeBuildHasher::new(seed).build_hasher();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[test]
+fn hasher_deterministic_and_keyed() {
+    let r = Record {
+        id: 7,
+        name: "seven",
+        tags: [1, 2, 3],
+    };
+    let other = Record {
+        id: 7,
+        name: "sevem",
+        tags: [1, 2, 3],
+    };
+
+    assert_eq!(hash_with_seed(b"seed", &r), hash_with_seed(b"seed", &r));
+    assert_ne!(hash_with_seed(b"seed", &r), hash_with_seed(b"seed", &other));
+    assert_ne!(hash_with_seed(b"seed", &r), hash_with_seed(b"deed", &r));
+}
+
+#[test]
+fn hasher_matches_strobe() {
+    let mut hasher = StrobeHasher::new(b"seed");
+    hasher.write(b"hello ");
+    hasher.write(b"world");
+    // finish doesn't consume anything, so it can be called repeatedly
+    assert_eq!(hasher.finish(), hasher.finish());
+
+    let mut s = Strobe::new(b"strobe-rs hasher", SecParam::B128);
+    s.key(b"seed", false);
+    s.ad(b"hello world", false);
+    assert_eq!(hasher.finish(), s.prf_u64());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn hasher_in_hash_map() {
+    let mut map = std::collections::HashMap::with_hasher(StrobeBuildHasher::new(b"seed"));
+    map.insert("one", 1);
+    map.insert("two", 2);
+    assert_eq!(map.get("one"), Some(&1));
+    assert_eq!(map.get("two"), Some(&2));
+    assert_eq!(map.get("three"), None);
+}
@@ -0,0 +1,155 @@
+use crate::strobe::{Strobe, IO_CHUNK_LEN};
+
+use std::io::{Read, Result, Write};
+
+use zeroize::Zeroize;
+
+/// A writer that encrypts everything written to it with `send_enc`, and forwards the ciphertext
+/// to an inner writer. Returned by [`Strobe::enc_writer`].
+///
+/// Writes are encrypted in a scratch buffer, so the caller's buffers are never modified. Once
+/// [`EncWriter::finish`] is called, the state is identical to that of a single
+/// `send_enc(data, false)` on the concatenation of everything written. If a write to the inner
+/// writer fails, the data passed to that call has already been encrypted, so the session must be
+/// abandoned.
+#[derive(Debug)]
+pub struct EncWriter<'a, W> {
+    strobe: &'a mut Strobe,
+    inner: W,
+    // Whether the operation has begun, i.e., whether the next write continues it
+    more: bool,
+}
+
+impl<'a, W: Write> EncWriter<'a, W> {
+    pub(crate) fn new(strobe: &'a mut Strobe, inner: W) -> EncWriter<'a, W> {
+        EncWriter {
+            strobe,
+            inner,
+            more: false,
+        }
+    }
+
+    /// Ends the encrypted message, flushes the inner writer, and returns it. If nothing was
+    /// written, this runs `send_enc` on an empty message, so that the state always reflects
+    /// exactly one `send_enc` operation.
+    pub fn finish(mut self) -> Result<W> {
+        if !self.more {
+            self.strobe.send_enc(&mut [], /* more */ false);
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<'a, W: Write> Write for EncWriter<'a, W> {
+    fn write(&mut self, data: &[u8]) -> Result<usize> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+
+        let mut buf = [0u8; IO_CHUNK_LEN];
+        let n = core::cmp::min(data.len(), buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        self.strobe.send_enc(&mut buf[..n], self.more);
+        self.more = true;
+
+        let res = self.inner.write_all(&buf[..n]);
+        // The buffer held plaintext before it was encrypted in place. Wipe it anyway.
+        buf.zeroize();
+        res.map(|_| n)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A reader that reads ciphertext from an inner reader, and decrypts it with `recv_enc`. Returned
+/// by [`Strobe::dec_reader`].
+///
+/// Once [`DecReader::finish`] is called, the state is identical to that of a single
+/// `recv_enc(data, false)` on the concatenation of everything read. Nothing here is
+/// authenticated, so the plaintext must not be trusted until a subsequent `recv_mac` succeeds.
+#[derive(Debug)]
+pub struct DecReader<'a, R> {
+    strobe: &'a mut Strobe,
+    inner: R,
+    // Whether the operation has begun, i.e., whether the next read continues it
+    more: bool,
+}
+
+impl<'a, R: Read> DecReader<'a, R> {
+    pub(crate) fn new(strobe: &'a mut Strobe, inner: R) -> DecReader<'a, R> {
+        DecReader {
+            strobe,
+            inner,
+            more: false,
+        }
+    }
+
+    /// Ends the encrypted message and returns the inner reader. If nothing was read, this runs
+    /// `recv_enc` on an empty message, so that the state always reflects exactly one `recv_enc`
+    /// operation.
+    pub fn finish(self) -> R {
+        if !self.more {
+            self.strobe.recv_enc(&mut [], /* more */ false);
+        }
+        self.inner
+    }
+}
+
+impl<'a, R: Read> Read for DecReader<'a, R> {
+    fn read(&mut self, out: &mut [u8]) -> Result<usize> {
+        let n = self.inner.read(out)?;
+        if n > 0 {
+            self.strobe.recv_enc(&mut out[..n], self.more);
+            self.more = true;
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+use crate::strobe::SecParam;
+
+#[test]
+fn io_adapters_match_one_shot() {
+    let mut tx = Strobe::new(b"io adapter test", SecParam::B256);
+    tx.key(b"the key", false);
+    let mut rx = tx.clone();
+    let mut one_shot = tx.clone();
+
+    let msg: std::vec::Vec<u8> = (0..2000u32).map(|i| (i * 13) as u8).collect();
+
+    // Write in uneven pieces, bigger and smaller than the scratch buffer
+    let mut w = tx.enc_writer(std::vec::Vec::new());
+    for piece in msg.chunks(700) {
+        w.write_all(piece).unwrap();
+    }
+    let ct = w.finish().unwrap();
+
+    let mut expected_ct = msg.clone();
+    one_shot.send_enc(&mut expected_ct, false);
+    assert_eq!(ct, expected_ct);
+    assert_eq!(tx.transcript_tag(), one_shot.transcript_tag());
+
+    // Decrypt through io::copy
+    let mut pt = std::vec::Vec::new();
+    let mut r = rx.dec_reader(&ct[..]);
+    std::io::copy(&mut r, &mut pt).unwrap();
+    r.finish();
+    assert_eq!(pt, msg);
+    assert_eq!(rx.transcript_tag(), tx.transcript_tag());
+}
+
+#[test]
+fn io_adapters_empty() {
+    let mut s = Strobe::new(b"io adapter test", SecParam::B256);
+    let mut one_shot = s.clone();
+
+    s.enc_writer(std::io::sink()).finish().unwrap();
+    s.dec_reader(std::io::empty()).finish();
+    one_shot.send_enc(&mut [], false);
+    one_shot.recv_enc(&mut [], false);
+    assert_eq!(s.transcript_tag(), one_shot.transcript_tag());
+}
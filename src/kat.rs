@@ -0,0 +1,240 @@
+#[cfg(feature = "self-test")]
+use crate::strobe::StrobeError;
+use crate::strobe::{
+    flags_for, OpFlags, SecParam, Strobe, AD_FLAGS, KEY_FLAGS, PRF_FLAGS, RATCHET_FLAGS,
+    RECV_CLR_FLAGS, RECV_ENC_FLAGS, RECV_MAC_FLAGS, SEND_CLR_FLAGS, SEND_ENC_FLAGS, SEND_MAC_FLAGS,
+};
+
+use alloc::{boxed::Box, string::String, vec::Vec};
+
+use serde::{de::Error as SError, Deserialize, Deserializer};
+
+// The test vectors, embedded so that they can be run without a filesystem
+pub(crate) const SIMPLE_TEST_VECTOR: &str = include_str!("../kat/simple_test_vector.json");
+pub(crate) const META_TEST_VECTOR: &str = include_str!("../kat/meta_test_vector.json");
+pub(crate) const STREAMING_TEST_VECTOR: &str = include_str!("../kat/streaming_test_vector.json");
+pub(crate) const BOUNDARY_TEST_VECTOR: &str = include_str!("../kat/boundary_test_vector.json");
+
+// This is the top-level structure of the JSON we find in the test vectors
+#[derive(Deserialize)]
+struct TestHead {
+    proto_string: String,
+    #[serde(deserialize_with = "sec_param_from_bits")]
+    security: SecParam,
+    operations: Vec<TestOp>,
+}
+
+// Each individual test case looks like this
+#[derive(Deserialize)]
+struct TestOp {
+    name: String,
+    meta: bool,
+    #[serde(deserialize_with = "bytes_from_hex")]
+    input_data: Vec<u8>,
+    stream: bool,
+    #[serde(default, rename = "output", deserialize_with = "bytes_from_hex_opt")]
+    expected_output: Option<Vec<u8>>,
+    #[serde(default, rename = "state_after", deserialize_with = "bytes_from_hex")]
+    expected_state_after: Vec<u8>,
+}
+
+// Tells serde how to deserialize a `SecParam`
+fn sec_param_from_bits<'de, D>(deserializer: D) -> Result<SecParam, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let b = u64::deserialize(deserializer)?;
+    match b {
+        128 => Ok(SecParam::B128),
+        256 => Ok(SecParam::B256),
+        n => Err(SError::custom(format_args!(
+            "Invalid security parameter: {}",
+            n
+        ))),
+    }
+}
+
+// Tells serde how to deserialize bytes from hex
+fn bytes_from_hex<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut hex_str = String::deserialize(deserializer)?;
+    // Prepend a 0 if it's not even length
+    if hex_str.len() % 2 == 1 {
+        hex_str.insert(0, '0');
+    }
+    hex::decode(hex_str).map_err(|e| SError::custom(format_args!("{:?}", e)))
+}
+
+// This function is a formality. Some fields are not present, so they're wrapped in Option in the
+// above structs. Hence, the deserialization function must return an Option. The `default` pragma
+// on the members ensures, however, that the value is None when the field is missing.
+fn bytes_from_hex_opt<'de, D>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    bytes_from_hex(deserializer).map(Some)
+}
+
+// Recall that `ratchet` can take a length argument, so this is the most general type that
+// represents the input to a STROBE operation
+enum DataOrLength<'a> {
+    Data(&'a mut [u8]),
+    Length(usize),
+}
+
+// A boxed closure that performs a single STROBE operation
+type BoxedOp = Box<dyn for<'a> Fn(&mut Strobe, DataOrLength<'a>, bool)>;
+
+// Given the flags of the operation and meta flag, returns a closure that performs this operation.
+// The types are kind of a mess, because the input and output types of the closure have to fit all
+// possible STROBE operations.
+fn get_op(flags: OpFlags, meta: bool) -> BoxedOp {
+    let f = move |s: &mut Strobe, dol: DataOrLength, more: bool| {
+        let data = match dol {
+            DataOrLength::Length(len) => {
+                assert_eq!(flags, RATCHET_FLAGS, "Got length input without RATCHET op");
+                if !meta {
+                    s.ratchet(len, more);
+                } else {
+                    s.meta_ratchet(len, more);
+                }
+                return;
+            }
+            DataOrLength::Data(data) => data,
+        };
+
+        // Note: we don't expect recv_MAC to work on random inputs. We test recv_MAC's
+        // correctness in strobe.rs. Also MAC sizes are 14 bytes in the KAT.
+        if !meta {
+            match flags {
+                AD_FLAGS => s.ad(data, more),
+                KEY_FLAGS => s.key(data, more),
+                PRF_FLAGS => s.prf(data, more),
+                SEND_CLR_FLAGS => s.send_clr(data, more),
+                RECV_CLR_FLAGS => s.recv_clr(data, more),
+                SEND_ENC_FLAGS => s.send_enc(data, more),
+                RECV_ENC_FLAGS => s.recv_enc(data, more),
+                SEND_MAC_FLAGS => s.send_mac(data, more),
+                RECV_MAC_FLAGS => s
+                    .recv_mac::<14>(data.as_ref().try_into().unwrap())
+                    .unwrap_or(()),
+                RATCHET_FLAGS => panic!("Got RATCHET op without length input"),
+                _ => panic!("Unexpected op flags: {:?}", flags),
+            }
+        } else {
+            match flags {
+                AD_FLAGS => s.meta_ad(data, more),
+                KEY_FLAGS => s.meta_key(data, more),
+                PRF_FLAGS => s.meta_prf(data, more),
+                SEND_CLR_FLAGS => s.meta_send_clr(data, more),
+                RECV_CLR_FLAGS => s.meta_recv_clr(data, more),
+                SEND_ENC_FLAGS => s.meta_send_enc(data, more),
+                RECV_ENC_FLAGS => s.meta_recv_enc(data, more),
+                SEND_MAC_FLAGS => s.meta_send_mac(data, more),
+                RECV_MAC_FLAGS => s
+                    .meta_recv_mac::<14>(data.as_ref().try_into().unwrap())
+                    .unwrap_or(()),
+                RATCHET_FLAGS => panic!("Got RATCHET op without length input"),
+                _ => panic!("Unexpected op flags: {:?}", flags),
+            }
+        }
+    };
+    Box::new(f)
+}
+
+// Runs the test vector and compares to the expected output at each step of the way. `round_trip`
+// is run on the state before every operation. On mismatch, returns the index of the first
+// operation whose state or output differs from the expected one.
+pub(crate) fn check_against_vector(
+    vector: &str,
+    round_trip: fn(Strobe) -> Strobe,
+) -> Result<(), usize> {
+    let TestHead {
+        proto_string,
+        security,
+        operations,
+    } = serde_json::from_str(vector).expect("malformed test vector");
+    let mut s = Strobe::new(proto_string.as_bytes(), security);
+
+    for (i, test_op) in operations.into_iter().enumerate() {
+        s = round_trip(s);
+
+        // Destructure the operation
+        let TestOp {
+            name,
+            meta,
+            mut input_data,
+            stream,
+            expected_output,
+            expected_state_after,
+        } = test_op;
+
+        if name != "init" {
+            // Op names are looked up in the same table the typed methods use
+            let flags = flags_for(&name).unwrap_or_else(|| panic!("Unexpected op name: {}", name));
+
+            // Keep the input around, to check which part of it the op overwrote
+            let orig_input = input_data.clone();
+
+            // RATCHET inputs are given as strings of zeros instead of lengths. So just take the
+            // length of the string of zeros.
+            let input = if flags == RATCHET_FLAGS {
+                DataOrLength::Length(input_data.len())
+            } else {
+                DataOrLength::Data(input_data.as_mut_slice())
+            };
+
+            let op = get_op(flags, meta);
+            op(&mut s, input, stream);
+
+            if s.st.0[..] != expected_state_after[..] {
+                return Err(i);
+            }
+
+            // Only test expected output if the test vector has output to test against. The vectors
+            // give the whole buffer after the op. The op overwrites the first `output_len` bytes of
+            // it, and must leave the rest as it was.
+            if let Some(eo) = expected_output {
+                let output_len = Strobe::output_len(flags, input_data.len());
+                if input_data != eo || input_data[output_len..] != orig_input[output_len..] {
+                    return Err(i);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs the known-answer test vectors from the [STROBE reference implementation][ref] and
+/// [StrobeGo][go] against this implementation, and returns an error describing the first mismatch.
+/// This is meant for startup health checks in the deployed environment.
+///
+/// [ref]: https://strobe.sourceforge.io/
+/// [go]: https://github.com/mimoo/StrobeGo
+#[cfg(feature = "self-test")]
+pub fn self_test() -> Result<(), StrobeError> {
+    let vectors = [
+        ("simple", SIMPLE_TEST_VECTOR),
+        ("meta", META_TEST_VECTOR),
+        ("streaming", STREAMING_TEST_VECTOR),
+        ("boundary", BOUNDARY_TEST_VECTOR),
+    ];
+
+    for (name, vector) in vectors {
+        check_against_vector(vector, |s| s).map_err(|operation| StrobeError::SelfTestFailed {
+            vector: name,
+            operation,
+        })?;
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "self-test")]
+#[test]
+fn self_test_passes() {
+    assert_eq!(self_test(), Ok(()));
+}
@@ -4,6 +4,9 @@ use zeroize::Zeroize;
 /// keccak block size in 64-bit words. This is the N parameter in the STROBE spec
 pub const KECCAK_BLOCK_SIZE: usize = 25;
 
+/// keccak block size in bytes
+pub const KECCAK_BLOCK_BYTES: usize = 8 * KECCAK_BLOCK_SIZE;
+
 // This is needed to make the version str
 pub(crate) const KECCAK_BLOCK_BITLEN_STR: &[u8] = b"1600";
 
@@ -28,10 +31,36 @@ pub(crate) struct AlignedKeccakState(
 // will optimize out the copy if we' re on a little endian machine. I don't feel comfortable doing
 // a mem transmute.
 pub(crate) fn keccakf_u8(st: &mut AlignedKeccakState) {
+    keccakf_bytes(&mut st.0);
+}
+
+// The permutation itself. This doesn't rely on the alignment of the buffer.
+fn keccakf_bytes(st: &mut [u8; 8 * KECCAK_BLOCK_SIZE]) {
     let mut keccak_block = [0u64; KECCAK_BLOCK_SIZE];
-    LittleEndian::read_u64_into(&st.0, &mut keccak_block);
+    LittleEndian::read_u64_into(st, &mut keccak_block);
+    #[cfg(not(any(
+        feature = "keccak-32bit",
+        all(target_arch = "arm", target_pointer_width = "32")
+    )))]
     keccak::f1600(&mut keccak_block);
-    LittleEndian::write_u64_into(&keccak_block, &mut st.0);
+    // The bit-interleaved implementation is the default on 32-bit ARM, where its rotations fold
+    // into other instructions for free. See the comment at the top of keccak32.rs.
+    #[cfg(any(
+        feature = "keccak-32bit",
+        all(target_arch = "arm", target_pointer_width = "32")
+    ))]
+    crate::keccak32::f1600(&mut keccak_block);
+    LittleEndian::write_u64_into(&keccak_block, st);
+}
+
+/// Performs the Keccak-f\[1600\] permutation on a 200-byte state, whose lanes are
+/// little-endian. This is exactly the permutation that `Strobe` uses internally.
+///
+/// **NOTE:** This is a low-level primitive, exposed for cross-checking other implementations. It
+/// is not covered by any stability guarantees, and may change or disappear in any release.
+#[cfg(feature = "internals")]
+pub fn keccak_f1600(state: &mut [u8; 8 * KECCAK_BLOCK_SIZE]) {
+    keccakf_bytes(state);
 }
 
 /*
@@ -65,3 +94,18 @@ fn zero_keccak() {
 
     assert_eq!(&state.0[..], &expected_output[..]);
 }
+
+// The public permutation should match the internal one
+#[cfg(feature = "internals")]
+#[test]
+fn public_keccak_matches_internal() {
+    let mut state = AlignedKeccakState([0u8; 8 * KECCAK_BLOCK_SIZE]);
+    for (i, b) in state.0.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+    let mut bytes = state.0;
+
+    keccakf_u8(&mut state);
+    keccak_f1600(&mut bytes);
+    assert_eq!(&state.0[..], &bytes[..]);
+}
@@ -0,0 +1,205 @@
+// A bit-interleaved implementation of Keccak-f[1600] using only 32-bit arithmetic. On 32-bit
+// targets, 64-bit rotations compile to several instructions each. Here, every 64-bit lane is
+// instead stored as two 32-bit words: one holding its even-indexed bits, and one holding its
+// odd-indexed bits. A 64-bit rotation then becomes a pair of 32-bit rotations. The output is
+// bit-identical to the 64-bit implementation.
+//
+// The rest of the crate works on the state as bytes, so the lanes are converted to and from the
+// interleaved form on every call. This is a real cost, so the conversion only uses 32-bit delta
+// swaps. On 32-bit ARM, 32-bit rotations fold into the shifted operand of other instructions, so
+// this is the default there. On i686, which has double-width shifts for 64-bit rotations, it
+// isn't. Use the `sized benches` in benches.rs, with and without the `keccak-32bit` feature, to
+// compare the two on a given target.
+
+use crate::keccak::KECCAK_BLOCK_SIZE;
+
+/// An interleaved lane: (even bits, odd bits)
+type Lane = (u32, u32);
+
+/// The Keccak-f[1600] round constants, as 64-bit lanes
+const RC: [u64; 24] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808a,
+    0x8000000080008000,
+    0x000000000000808b,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008a,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000a,
+    0x000000008000808b,
+    0x800000000000008b,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800a,
+    0x800000008000000a,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+
+/// The rho rotation offsets, indexed by lane `x + 5*y`
+const RHO: [u32; 25] = [
+    0, 1, 62, 28, 27, 36, 44, 6, 55, 20, 3, 10, 43, 25, 39, 41, 45, 15, 21, 8, 18, 2, 61, 56, 14,
+];
+
+/// Splits a 64-bit lane into its even-indexed and odd-indexed bits. This only uses 32-bit
+/// arithmetic, since it runs on every lane for every permutation.
+const fn interleave(x: u64) -> Lane {
+    let lo = unshuffle(x as u32);
+    let hi = unshuffle((x >> 32) as u32);
+    ((lo & 0xffff) | (hi << 16), (lo >> 16) | (hi & 0xffff_0000))
+}
+
+/// Merges an interleaved lane back into a 64-bit lane. This is the inverse of `interleave`.
+fn deinterleave((even, odd): Lane) -> u64 {
+    let lo = shuffle((even & 0xffff) | (odd << 16));
+    let hi = shuffle((even >> 16) | (odd & 0xffff_0000));
+    u64::from(lo) | (u64::from(hi) << 32)
+}
+
+/// Moves the even-indexed bits of `x` to its low half and the odd-indexed bits to its high half,
+/// with a sequence of delta swaps (see Hacker's Delight, 7-2)
+const fn unshuffle(mut x: u32) -> u32 {
+    let mut t;
+    t = (x ^ (x >> 1)) & 0x2222_2222;
+    x ^= t ^ (t << 1);
+    t = (x ^ (x >> 2)) & 0x0c0c_0c0c;
+    x ^= t ^ (t << 2);
+    t = (x ^ (x >> 4)) & 0x00f0_00f0;
+    x ^= t ^ (t << 4);
+    t = (x ^ (x >> 8)) & 0x0000_ff00;
+    x ^= t ^ (t << 8);
+    x
+}
+
+/// The inverse of `unshuffle`. This runs the same delta swaps in reverse order.
+fn shuffle(mut x: u32) -> u32 {
+    let mut t;
+    t = (x ^ (x >> 8)) & 0x0000_ff00;
+    x ^= t ^ (t << 8);
+    t = (x ^ (x >> 4)) & 0x00f0_00f0;
+    x ^= t ^ (t << 4);
+    t = (x ^ (x >> 2)) & 0x0c0c_0c0c;
+    x ^= t ^ (t << 2);
+    t = (x ^ (x >> 1)) & 0x2222_2222;
+    x ^= t ^ (t << 1);
+    x
+}
+
+/// The round constants, interleaved
+const RC_INTERLEAVED: [Lane; 24] = {
+    let mut out = [(0u32, 0u32); 24];
+    let mut i = 0;
+    while i < 24 {
+        out[i] = interleave(RC[i]);
+        i += 1;
+    }
+    out
+};
+
+/// Rotates an interleaved lane left by `r` bits, as a 64-bit lane
+fn rotl((even, odd): Lane, r: u32) -> Lane {
+    if r % 2 == 0 {
+        (even.rotate_left(r / 2), odd.rotate_left(r / 2))
+    } else {
+        // Even bits move to odd positions and vice versa
+        (odd.rotate_left((r + 1) / 2), even.rotate_left(r / 2))
+    }
+}
+
+fn xor(a: Lane, b: Lane) -> Lane {
+    (a.0 ^ b.0, a.1 ^ b.1)
+}
+
+/// Performs the Keccak-f[1600] permutation on 25 64-bit lanes, using 32-bit arithmetic
+pub(crate) fn f1600(lanes: &mut [u64; KECCAK_BLOCK_SIZE]) {
+    let mut a = [(0u32, 0u32); KECCAK_BLOCK_SIZE];
+    for (a, lane) in a.iter_mut().zip(lanes.iter()) {
+        *a = interleave(*lane);
+    }
+
+    for rc in RC_INTERLEAVED.iter() {
+        // Theta
+        let mut c = [(0u32, 0u32); 5];
+        for (x, c) in c.iter_mut().enumerate() {
+            *c = xor(
+                xor(xor(a[x], a[x + 5]), xor(a[x + 10], a[x + 15])),
+                a[x + 20],
+            );
+        }
+        for x in 0..5 {
+            let d = xor(c[(x + 4) % 5], rotl(c[(x + 1) % 5], 1));
+            for y in 0..5 {
+                a[x + 5 * y] = xor(a[x + 5 * y], d);
+            }
+        }
+
+        // Rho and pi
+        let mut b = [(0u32, 0u32); KECCAK_BLOCK_SIZE];
+        for x in 0..5 {
+            for y in 0..5 {
+                b[y + 5 * ((2 * x + 3 * y) % 5)] = rotl(a[x + 5 * y], RHO[x + 5 * y]);
+            }
+        }
+
+        // Chi
+        for y in 0..5 {
+            for x in 0..5 {
+                let (b0, b1, b2) = (b[x + 5 * y], b[(x + 1) % 5 + 5 * y], b[(x + 2) % 5 + 5 * y]);
+                a[x + 5 * y] = (b0.0 ^ (!b1.0 & b2.0), b0.1 ^ (!b1.1 & b2.1));
+            }
+        }
+
+        // Iota
+        a[0] = xor(a[0], *rc);
+    }
+
+    for (lane, a) in lanes.iter_mut().zip(a.iter()) {
+        *lane = deinterleave(*a);
+    }
+}
+
+#[test]
+fn interleave_round_trip() {
+    for x in [
+        0u64,
+        1,
+        2,
+        0x8000_0000_0000_0000,
+        0x0123_4567_89ab_cdef,
+        u64::MAX,
+    ] {
+        assert_eq!(deinterleave(interleave(x)), x);
+        for r in 0..64 {
+            assert_eq!(deinterleave(rotl(interleave(x), r)), x.rotate_left(r));
+        }
+    }
+}
+
+// The interleaved permutation must be bit-identical to the 64-bit one
+#[test]
+fn matches_64bit_permutation() {
+    let mut lanes = [0u64; KECCAK_BLOCK_SIZE];
+    let mut expected = lanes;
+    for _ in 0..4 {
+        f1600(&mut lanes);
+        keccak::f1600(&mut expected);
+        assert_eq!(lanes, expected);
+    }
+
+    let mut lanes = [0u64; KECCAK_BLOCK_SIZE];
+    for (i, lane) in lanes.iter_mut().enumerate() {
+        *lane = (i as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    }
+    let mut expected = lanes;
+    f1600(&mut lanes);
+    keccak::f1600(&mut expected);
+    assert_eq!(lanes, expected);
+}
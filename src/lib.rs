@@ -10,12 +10,19 @@
 #[macro_use]
 extern crate std;
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
 // An Error type is just something that's Debug and Display
 #[cfg(feature = "std")]
 impl std::error::Error for AuthError {}
+#[cfg(feature = "std")]
+impl std::error::Error for StrobeError {}
 
 //-------- Testing stuff --------//
+// Some of the original MAC tests pass arrays through a no-op `try_into`
 #[cfg(test)]
+#[allow(clippy::useless_conversion)]
 mod basic_tests;
 
 // kat_tests requires std
@@ -24,7 +31,47 @@ mod kat_tests;
 
 //-------- Modules and exports--------//
 
+mod chain;
+mod hasher;
+#[cfg(feature = "std")]
+mod io;
+#[cfg(feature = "alloc")]
+mod iter;
+// The KAT machinery is shared between the tests and the runtime self-test
+#[cfg(any(feature = "self-test", all(test, feature = "std")))]
+mod kat;
 mod keccak;
+#[cfg(any(
+    feature = "keccak-32bit",
+    all(target_arch = "arm", target_pointer_width = "32"),
+    test
+))]
+mod keccak32;
+mod rekey;
+#[cfg(feature = "rng")]
+mod rng;
+mod stream;
 mod strobe;
+mod typed;
+#[cfg(feature = "digest")]
+mod xof;
 
+pub use crate::chain::*;
+pub use crate::hasher::*;
+#[cfg(feature = "std")]
+pub use crate::io::*;
+#[cfg(feature = "alloc")]
+pub use crate::iter::*;
+#[cfg(feature = "self-test")]
+pub use crate::kat::self_test;
+#[cfg(feature = "internals")]
+pub use crate::keccak::keccak_f1600;
+pub use crate::keccak::{KECCAK_BLOCK_BYTES, KECCAK_BLOCK_SIZE};
+pub use crate::rekey::*;
+#[cfg(feature = "rng")]
+pub use crate::rng::*;
+pub use crate::stream::*;
 pub use crate::strobe::*;
+pub use crate::typed::*;
+#[cfg(feature = "digest")]
+pub use crate::xof::*;
@@ -0,0 +1,166 @@
+// A wrapper around `Strobe` that rekeys from a root key every so many encryptions, so that no
+// single key is used for an unbounded number of messages
+
+use crate::strobe::Strobe;
+
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// A `Strobe` that automatically rekeys after every `threshold` calls to `send_enc`/`recv_enc`.
+///
+/// Rekeying runs `ratchet_full`, then `meta_ad(b"rekey")`, then `meta_ad` on the new epoch number
+/// as an 8-byte little-endian integer, then `key(root_key, false)`. The epoch starts at 0 and is
+/// incremented by every rekey. Since the message count only depends on how many `send_enc` and
+/// `recv_enc` calls were made, two peers that construct their `RekeyingStrobe`s identically and
+/// perform the same sequence of operations rekey at exactly the same points. Operations done
+/// through [`RekeyingStrobe::strobe_mut`] are not counted.
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct RekeyingStrobe {
+    strobe: Strobe,
+    root_key: [u8; 32],
+    #[zeroize(skip)]
+    threshold: u64,
+    #[zeroize(skip)]
+    count: u64,
+    #[zeroize(skip)]
+    epoch: u64,
+}
+
+// The root key is secret, so we never print it
+impl core::fmt::Debug for RekeyingStrobe {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("RekeyingStrobe")
+            .field("strobe", &self.strobe)
+            .field("threshold", &self.threshold)
+            .field("count", &self.count)
+            .field("epoch", &self.epoch)
+            .finish()
+    }
+}
+
+impl RekeyingStrobe {
+    /// Makes a new `RekeyingStrobe` that keys `strobe` with `root_key`, and rekeys after every
+    /// `threshold` encryptions or decryptions.
+    ///
+    /// Panics if `threshold` is 0.
+    pub fn new(mut strobe: Strobe, root_key: [u8; 32], threshold: u64) -> RekeyingStrobe {
+        assert!(threshold > 0, "rekey threshold must be nonzero");
+        strobe.key(root_key, /* more */ false);
+
+        RekeyingStrobe {
+            strobe,
+            root_key,
+            threshold,
+            count: 0,
+            epoch: 0,
+        }
+    }
+
+    /// Returns the number of rekeys that have happened so far
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Returns the number of `send_enc`/`recv_enc` calls left before the next rekey
+    pub fn messages_until_rekey(&self) -> u64 {
+        self.threshold - self.count
+    }
+
+    /// Returns a reference to the underlying `Strobe`
+    pub fn strobe(&self) -> &Strobe {
+        &self.strobe
+    }
+
+    /// Returns a mutable reference to the underlying `Strobe`, e.g., for running `ad` or
+    /// `send_mac`. Operations done through this reference do not count towards the threshold.
+    pub fn strobe_mut(&mut self) -> &mut Strobe {
+        &mut self.strobe
+    }
+
+    /// Sends an encrypted message, as in `Strobe::send_enc(data, false)`, then rekeys if this was
+    /// the `threshold`-th message since the last rekey
+    pub fn send_enc(&mut self, data: &mut [u8]) {
+        self.strobe.send_enc(data, /* more */ false);
+        self.count_message();
+    }
+
+    /// Receives an encrypted message, as in `Strobe::recv_enc(data, false)`, then rekeys if this
+    /// was the `threshold`-th message since the last rekey
+    pub fn recv_enc(&mut self, data: &mut [u8]) {
+        self.strobe.recv_enc(data, /* more */ false);
+        self.count_message();
+    }
+
+    // Counts one message, and rekeys if the threshold is reached
+    fn count_message(&mut self) {
+        self.count += 1;
+        if self.count == self.threshold {
+            self.rekey();
+        }
+    }
+
+    // Forgets the current key and derives the next epoch's key from the root key
+    fn rekey(&mut self) {
+        self.epoch = self.epoch.checked_add(1).expect("rekey epoch overflowed");
+        self.count = 0;
+
+        self.strobe.ratchet_full();
+        self.strobe.meta_ad(b"rekey", false);
+        self.strobe.meta_ad(self.epoch.to_le_bytes(), false);
+        self.strobe.key(self.root_key, false);
+    }
+}
+
+#[cfg(test)]
+use crate::strobe::SecParam;
+
+#[cfg(test)]
+fn rekeying_pair(threshold: u64) -> (RekeyingStrobe, RekeyingStrobe) {
+    let s = Strobe::new(b"rekeytest", SecParam::B256);
+    let tx = RekeyingStrobe::new(s.clone(), [7u8; 32], threshold);
+    let rx = RekeyingStrobe::new(s, [7u8; 32], threshold);
+    (tx, rx)
+}
+
+#[test]
+fn rekey_stays_in_sync() {
+    let (mut tx, mut rx) = rekeying_pair(3);
+
+    for i in 0..10u64 {
+        let orig = i.to_le_bytes();
+        let mut msg = orig;
+        tx.send_enc(&mut msg);
+        rx.recv_enc(&mut msg);
+        assert_eq!(msg, orig);
+        assert_eq!(tx.epoch(), (i + 1) / 3);
+        assert_eq!(tx.strobe().transcript_tag(), rx.strobe().transcript_tag());
+    }
+    assert_eq!(tx.messages_until_rekey(), 2);
+
+    let mut mac = [0u8; 16];
+    tx.strobe_mut().send_mac(&mut mac, false);
+    rx.strobe_mut().recv_mac(&mac).unwrap();
+}
+
+#[test]
+fn rekey_matches_manual() {
+    let (mut tx, _) = rekeying_pair(2);
+    let mut manual = Strobe::new(b"rekeytest", SecParam::B256);
+    manual.key([7u8; 32], false);
+
+    let mut msg1 = *b"one";
+    let mut msg2 = *b"two";
+    tx.send_enc(&mut msg1);
+    tx.send_enc(&mut msg2);
+
+    let mut buf = *b"one";
+    manual.send_enc(&mut buf, false);
+    let mut buf = *b"two";
+    manual.send_enc(&mut buf, false);
+    manual.ratchet_full();
+    manual.meta_ad(b"rekey", false);
+    manual.meta_ad(1u64.to_le_bytes(), false);
+    manual.key([7u8; 32], false);
+
+    assert_eq!(tx.epoch(), 1);
+    assert_eq!(tx.strobe().transcript_tag(), manual.transcript_tag());
+}
@@ -0,0 +1,150 @@
+use crate::strobe::{SecParam, Strobe};
+
+use rand_core::{CryptoRng, RngCore, SeedableRng};
+
+/// The protocol string used for seeding a `StrobeRng` via `SeedableRng::from_seed`
+const SEED_PROTO: &[u8] = b"StrobeRng seed";
+
+/// The labels that `StrobeRng::fork_rng` uses to separate the original RNG from the forked one
+const FORK_PARENT_LABEL: &[u8] = b"StrobeRng fork parent";
+const FORK_CHILD_LABEL: &[u8] = b"StrobeRng fork child";
+
+/// The number of bytes of state zeroed by the ratchet in `StrobeRng::reseed`. This is enough to
+/// cover 256 bits of security.
+const RESEED_RATCHET_LEN: usize = 32;
+
+/// A cryptographically secure RNG whose output is the PRF output of the underlying `Strobe`. A
+/// `StrobeRng` is constructed from a `Strobe` via `From`, or from a seed via `SeedableRng`.
+///
+/// **NOTE:** The output of a `StrobeRng` is only as unpredictable as the state of the `Strobe` it
+/// was made from. Make sure the state has been keyed with secret data before using it.
+///
+/// **WARNING:** Two `StrobeRng`s made from copies of the same `Strobe` produce identical output.
+/// Do not `clone()` a `Strobe` and convert both copies. If you need more than one RNG from a single
+/// state, use [`StrobeRng::fork_rng`], which domain-separates the two.
+#[derive(Clone, Debug)]
+pub struct StrobeRng {
+    strobe: Strobe,
+}
+
+impl From<Strobe> for StrobeRng {
+    fn from(strobe: Strobe) -> StrobeRng {
+        StrobeRng { strobe }
+    }
+}
+
+impl StrobeRng {
+    /// Mixes `extra` into the RNG state without discarding the existing state. This keys the
+    /// underlying `Strobe` with `extra`, then explicitly ratchets it.
+    ///
+    /// The ratchet makes this forward-secure: someone who learns the state after the reseed cannot
+    /// roll it back to recover the state, or the outputs, from before the reseed.
+    pub fn reseed(&mut self, extra: &[u8]) {
+        self.strobe.key(extra, false);
+        self.strobe.ratchet(RESEED_RATCHET_LEN, false);
+    }
+
+    /// Returns a new `StrobeRng` whose output is independent of this one's. The current state is
+    /// copied, and the copy and the original are each mixed with a distinct label, so the two
+    /// streams diverge.
+    pub fn fork_rng(&mut self) -> StrobeRng {
+        let mut child = self.strobe.clone();
+        self.strobe.meta_ad(FORK_PARENT_LABEL, false);
+        child.meta_ad(FORK_CHILD_LABEL, false);
+        child.into()
+    }
+}
+
+impl RngCore for StrobeRng {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.strobe.fill_bytes(dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for StrobeRng {}
+
+impl SeedableRng for StrobeRng {
+    type Seed = [u8; 32];
+
+    /// Makes a `StrobeRng` from a `Strobe` with protocol string `"StrobeRng seed"` and security
+    /// parameter `B128`, keyed with `seed`
+    fn from_seed(seed: Self::Seed) -> StrobeRng {
+        let mut strobe = Strobe::new(SEED_PROTO, SecParam::B128);
+        strobe.key(seed, false);
+        strobe.into()
+    }
+}
+
+#[test]
+fn seeded_rng() {
+    let mut rng1 = StrobeRng::from_seed([1u8; 32]);
+    let mut rng2 = StrobeRng::from_seed([1u8; 32]);
+    let mut rng3 = StrobeRng::from_seed([2u8; 32]);
+
+    let mut out1 = [0u8; 64];
+    let mut out2 = [0u8; 64];
+    let mut out3 = [0u8; 64];
+    rng1.fill_bytes(&mut out1);
+    rng2.fill_bytes(&mut out2);
+    rng3.fill_bytes(&mut out3);
+
+    // Identical seeds produce identical streams, and different seeds diverge
+    assert_eq!(out1, out2);
+    assert_ne!(out1, out3);
+    assert_eq!(rng1.next_u64(), rng2.next_u64());
+    assert_ne!(rng1.next_u64(), rng3.next_u64());
+}
+
+#[test]
+fn reseeded_rng() {
+    let mut rng1 = StrobeRng::from_seed([1u8; 32]);
+    let mut rng2 = rng1.clone();
+
+    // Same prior state, but only one of them gets reseeded
+    rng2.reseed(b"fresh entropy");
+
+    let mut out1 = [0u8; 64];
+    let mut out2 = [0u8; 64];
+    rng1.fill_bytes(&mut out1);
+    rng2.fill_bytes(&mut out2);
+    assert_ne!(out1, out2);
+}
+
+#[test]
+fn forked_rng() {
+    let mut parent = StrobeRng::from_seed([3u8; 32]);
+    let mut child = parent.fork_rng();
+    let mut grandchild = child.fork_rng();
+
+    let mut out1 = [0u8; 64];
+    let mut out2 = [0u8; 64];
+    let mut out3 = [0u8; 64];
+    parent.fill_bytes(&mut out1);
+    child.fill_bytes(&mut out2);
+    grandchild.fill_bytes(&mut out3);
+
+    assert_ne!(out1, out2);
+    assert_ne!(out1, out3);
+    assert_ne!(out2, out3);
+
+    // Forking is deterministic
+    let mut parent = StrobeRng::from_seed([3u8; 32]);
+    let mut child = parent.fork_rng();
+    let _ = child.fork_rng();
+    let mut out4 = [0u8; 64];
+    child.fill_bytes(&mut out4);
+    assert_eq!(out2, out4);
+}
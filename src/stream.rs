@@ -0,0 +1,192 @@
+// A STREAM-style chunked AEAD built on `Strobe`, in the spirit of the `aead::stream`
+// construction
+
+use crate::strobe::{Strobe, StrobeError};
+
+/// The length, in bytes, of the tag that authenticates each chunk
+pub const STREAM_TAG_LEN: usize = 16;
+
+/// Builds the header that's mixed in before each chunk
+fn chunk_header(counter: u64, is_last: bool) -> [u8; 9] {
+    let mut header = [0u8; 9];
+    header[..8].copy_from_slice(&counter.to_le_bytes());
+    header[8] = is_last as u8;
+    header
+}
+
+/// The encrypting half of a chunked AEAD stream. The `Strobe` it's made from should already be
+/// keyed, and should have absorbed a nonce if the key is ever reused.
+///
+/// Each chunk is individually authenticated, and chunks are bound to their position in the
+/// stream, so reordering, dropping, or truncating chunks is detected by the `OpenStream`. Before
+/// each chunk, both sides run `meta_ad` on a 9-byte chunk header: the chunk counter as an 8-byte
+/// little-endian integer, followed by `0x01` if the chunk is the last one and `0x00` otherwise.
+/// The chunk is then encrypted with `send_enc` and authenticated with a [`STREAM_TAG_LEN`]-byte
+/// `send_mac`.
+#[derive(Clone, Debug)]
+pub struct SealStream {
+    strobe: Strobe,
+    counter: u64,
+}
+
+impl SealStream {
+    /// Makes a new `SealStream` that encrypts with the given `Strobe`
+    pub fn new(strobe: Strobe) -> SealStream {
+        SealStream { strobe, counter: 0 }
+    }
+
+    // Encrypts one chunk in place and returns its tag
+    fn seal(&mut self, chunk: &mut [u8], is_last: bool) -> [u8; STREAM_TAG_LEN] {
+        let counter = self.counter;
+        self.counter = counter
+            .checked_add(1)
+            .expect("stream chunk counter overflowed");
+
+        self.strobe.meta_ad(chunk_header(counter, is_last), false);
+        self.strobe.send_enc(chunk, false);
+        let mut tag = [0u8; STREAM_TAG_LEN];
+        self.strobe.send_mac(&mut tag, false);
+        tag
+    }
+
+    /// Encrypts a chunk in place that is not the last chunk of the stream, and returns its tag
+    pub fn encrypt_chunk(&mut self, chunk: &mut [u8]) -> [u8; STREAM_TAG_LEN] {
+        self.seal(chunk, /* is_last */ false)
+    }
+
+    /// Encrypts the last chunk of the stream in place, and returns its tag. The last chunk may be
+    /// empty.
+    pub fn finalize(mut self, last_chunk: &mut [u8]) -> [u8; STREAM_TAG_LEN] {
+        self.seal(last_chunk, /* is_last */ true)
+    }
+}
+
+/// The decrypting half of a chunked AEAD stream. The `Strobe` it's made from must be in the same
+/// state as the one the corresponding `SealStream` was made from.
+#[derive(Clone, Debug)]
+pub struct OpenStream {
+    strobe: Strobe,
+    counter: u64,
+    /// Whether the last chunk has been successfully decrypted
+    saw_last: bool,
+    /// Whether any chunk failed to decrypt. Once this is set, every operation fails.
+    poisoned: bool,
+}
+
+impl OpenStream {
+    /// Makes a new `OpenStream` that decrypts with the given `Strobe`
+    pub fn new(strobe: Strobe) -> OpenStream {
+        OpenStream {
+            strobe,
+            counter: 0,
+            saw_last: false,
+            poisoned: false,
+        }
+    }
+
+    /// Decrypts a chunk in place and checks its tag. `is_last` says whether this is the last
+    /// chunk of the stream.
+    ///
+    /// Returns `StrobeError::MacMismatch` if the tag does not verify, in which case the contents
+    /// of `chunk` must be discarded. After any failure, or after the last chunk, every subsequent
+    /// call returns an error.
+    pub fn decrypt_chunk(
+        &mut self,
+        chunk: &mut [u8],
+        tag: &[u8; STREAM_TAG_LEN],
+        is_last: bool,
+    ) -> Result<(), StrobeError> {
+        if self.poisoned {
+            return Err(StrobeError::MacMismatch);
+        }
+        if self.saw_last {
+            return Err(StrobeError::InvalidState);
+        }
+
+        let counter = self.counter;
+        self.counter = counter
+            .checked_add(1)
+            .expect("stream chunk counter overflowed");
+
+        self.strobe.meta_ad(chunk_header(counter, is_last), false);
+        self.strobe.recv_enc(chunk, false);
+        if let Err(e) = self.strobe.recv_mac(tag) {
+            self.poisoned = true;
+            return Err(e.into());
+        }
+
+        self.saw_last = is_last;
+        Ok(())
+    }
+
+    /// Checks that the stream ended properly, i.e., that the last chunk was decrypted and no
+    /// chunk failed. This is what detects truncation of the stream at a chunk boundary.
+    pub fn finalize(self) -> Result<(), StrobeError> {
+        if self.poisoned || !self.saw_last {
+            Err(StrobeError::MacMismatch)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+use crate::strobe::SecParam;
+
+#[cfg(test)]
+fn keyed_strobe() -> Strobe {
+    let mut s = Strobe::new(b"streamtest", SecParam::B256);
+    s.key(b"secretsauce", false);
+    s
+}
+
+#[test]
+fn stream_round_trip() {
+    let orig_chunks = [*b"chunk one", *b"chunk two", *b"chunk 3!!"];
+    let mut chunks = orig_chunks;
+    let mut tags = [[0u8; STREAM_TAG_LEN]; 3];
+
+    let mut sealer = SealStream::new(keyed_strobe());
+    tags[0] = sealer.encrypt_chunk(&mut chunks[0]);
+    tags[1] = sealer.encrypt_chunk(&mut chunks[1]);
+    tags[2] = sealer.finalize(&mut chunks[2]);
+
+    let mut opener = OpenStream::new(keyed_strobe());
+    for (i, (chunk, tag)) in chunks.iter_mut().zip(tags.iter()).enumerate() {
+        opener.decrypt_chunk(chunk, tag, i == 2).unwrap();
+    }
+    opener.finalize().unwrap();
+    assert_eq!(chunks, orig_chunks);
+}
+
+#[test]
+fn stream_reorder_and_truncation() {
+    let mut chunks = [*b"chunk one", *b"chunk two", *b"chunk 3!!"];
+    let mut tags = [[0u8; STREAM_TAG_LEN]; 3];
+
+    let mut sealer = SealStream::new(keyed_strobe());
+    tags[0] = sealer.encrypt_chunk(&mut chunks[0]);
+    tags[1] = sealer.encrypt_chunk(&mut chunks[1]);
+    tags[2] = sealer.finalize(&mut chunks[2]);
+
+    // Swapping two chunks fails
+    let mut opener = OpenStream::new(keyed_strobe());
+    let mut chunk = chunks[1];
+    assert!(opener.decrypt_chunk(&mut chunk, &tags[1], false).is_err());
+    assert!(opener.finalize().is_err());
+
+    // Dropping the last chunk is caught at finalize
+    let mut opener = OpenStream::new(keyed_strobe());
+    for i in 0..2 {
+        let mut chunk = chunks[i];
+        opener.decrypt_chunk(&mut chunk, &tags[i], false).unwrap();
+    }
+    assert!(opener.finalize().is_err());
+
+    // Claiming a middle chunk is the last one fails
+    let mut opener = OpenStream::new(keyed_strobe());
+    let mut chunk = chunks[0];
+    opener.decrypt_chunk(&mut chunk, &tags[0], false).unwrap();
+    let mut chunk = chunks[1];
+    assert!(opener.decrypt_chunk(&mut chunk, &tags[1], true).is_err());
+}
@@ -1,7 +1,21 @@
-use crate::keccak::{keccakf_u8, AlignedKeccakState, KECCAK_BLOCK_BITLEN_STR, KECCAK_BLOCK_SIZE};
+use crate::chain::Chain;
+#[cfg(feature = "std")]
+use crate::io::{DecReader, EncWriter};
+#[cfg(feature = "alloc")]
+use crate::iter::EncIter;
+use crate::keccak::{
+    keccakf_u8, AlignedKeccakState, KECCAK_BLOCK_BITLEN_STR, KECCAK_BLOCK_BYTES, KECCAK_BLOCK_SIZE,
+};
+
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
 
 use bitflags::bitflags;
-use subtle::{self, ConstantTimeEq};
+use core::mem::MaybeUninit;
+#[cfg(feature = "generic-array")]
+use generic_array::{ArrayLength, GenericArray};
+#[cfg(feature = "subtle")]
+use subtle::{self, ConditionallySelectable, ConstantTimeEq};
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
 // With this feature on, a user can serialize and deserialize the state of a STROBE session
@@ -11,14 +25,29 @@ use serde::{Deserialize, Serialize};
 /// Version of Strobe that this crate implements.
 pub const STROBE_VERSION: &[u8] = b"1.0.2";
 
+/// The size, in bytes, of the blocks that `Strobe::keystream_at` indexes its output by
+pub const KEYSTREAM_BLOCK_LEN: usize = 64;
+
+/// The minimum MAC length, in bytes, that `Strobe::send_mac_checked` will produce. A 16-byte MAC
+/// gives 128 bits of security against forgery.
+pub const MIN_MAC_LEN: usize = 16;
+
 /// A placeholder for STROBE version strings. This is the length of the real version strings, for
 /// Keccak-f[1600]
 const TEMPLATE_VERSION_STR: [u8; 29] = *b"Strobe-Keccak-sss/bbbb-vX.Y.Z";
 
+// The size of the stack buffer used when reading from and writing to streams
+#[cfg(feature = "std")]
+pub(crate) const IO_CHUNK_LEN: usize = 512;
+
+/// The length of the output of `Strobe::to_bytes`. This is the Keccak state, followed by 17 bytes
+/// of bookkeeping, followed by a 4-byte checksum.
+#[cfg(all(feature = "serialize_secret_state", feature = "alloc"))]
+const SERIALIZED_STATE_LEN: usize = 8 * KECCAK_BLOCK_SIZE + 17 + 4;
+
 bitflags! {
     /// Operation flags defined in the Strobe paper. This is defined as a bitflags struct.
-    #[cfg_attr(feature = "serialize_secret_state", derive(Serialize, Deserialize))]
-    pub(crate) struct OpFlags: u8 {
+    pub struct OpFlags: u8 {
         /// Is data being moved inbound
         const I = 1<<0;
         /// Is data being sent to the application
@@ -34,28 +63,249 @@ bitflags! {
     }
 }
 
+// The flags of each of the typed operations. The meta variants additionally set `OpFlags::M`.
+
+/// The flags of `ad`
+pub const AD_FLAGS: OpFlags = OpFlags::A;
+/// The flags of `key`
+pub const KEY_FLAGS: OpFlags = OpFlags::from_bits_truncate(OpFlags::A.bits() | OpFlags::C.bits());
+/// The flags of `prf`
+pub const PRF_FLAGS: OpFlags =
+    OpFlags::from_bits_truncate(OpFlags::I.bits() | OpFlags::A.bits() | OpFlags::C.bits());
+/// The flags of `send_clr`
+pub const SEND_CLR_FLAGS: OpFlags =
+    OpFlags::from_bits_truncate(OpFlags::A.bits() | OpFlags::T.bits());
+/// The flags of `recv_clr`
+pub const RECV_CLR_FLAGS: OpFlags =
+    OpFlags::from_bits_truncate(OpFlags::I.bits() | OpFlags::A.bits() | OpFlags::T.bits());
+/// The flags of `send_enc`
+pub const SEND_ENC_FLAGS: OpFlags =
+    OpFlags::from_bits_truncate(OpFlags::A.bits() | OpFlags::C.bits() | OpFlags::T.bits());
+/// The flags of `recv_enc`
+pub const RECV_ENC_FLAGS: OpFlags = OpFlags::from_bits_truncate(
+    OpFlags::I.bits() | OpFlags::A.bits() | OpFlags::C.bits() | OpFlags::T.bits(),
+);
+/// The flags of `send_mac`
+pub const SEND_MAC_FLAGS: OpFlags =
+    OpFlags::from_bits_truncate(OpFlags::C.bits() | OpFlags::T.bits());
+/// The flags of `recv_mac`
+pub const RECV_MAC_FLAGS: OpFlags =
+    OpFlags::from_bits_truncate(OpFlags::I.bits() | OpFlags::C.bits() | OpFlags::T.bits());
+/// The flags of `ratchet`
+pub const RATCHET_FLAGS: OpFlags = OpFlags::C;
+
+/// Returns the flags of the operation with the given name, as the operation is named in the STROBE
+/// specification and the known-answer test vectors, e.g., `"AD"`, `"KEY"`, or `"send_ENC"`.
+/// Returns `None` if the name is unknown. The returned flags are those of the non-meta variant.
+pub fn flags_for(op: &str) -> Option<OpFlags> {
+    match op {
+        "AD" => Some(AD_FLAGS),
+        "KEY" => Some(KEY_FLAGS),
+        "PRF" => Some(PRF_FLAGS),
+        "send_CLR" => Some(SEND_CLR_FLAGS),
+        "recv_CLR" => Some(RECV_CLR_FLAGS),
+        "send_ENC" => Some(SEND_ENC_FLAGS),
+        "recv_ENC" => Some(RECV_ENC_FLAGS),
+        "send_MAC" => Some(SEND_MAC_FLAGS),
+        "recv_MAC" => Some(RECV_MAC_FLAGS),
+        "RATCHET" => Some(RATCHET_FLAGS),
+        _ => None,
+    }
+}
+
 impl Zeroize for OpFlags {
     fn zeroize(&mut self) {
         self.bits.zeroize()
     }
 }
 
-/// Security parameter. Choice of 128 or 256 bits.
-#[derive(Clone, Copy)]
-#[cfg_attr(feature = "serialize_secret_state", derive(Serialize, Deserialize))]
+// OpFlags serialize as their bits. Unknown bits and the unimplemented K flag are rejected.
+#[cfg(feature = "serialize_secret_state")]
+impl Serialize for OpFlags {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.bits().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serialize_secret_state")]
+impl<'de> Deserialize<'de> for OpFlags {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bits = u8::deserialize(deserializer)?;
+        match OpFlags::from_bits(bits) {
+            Some(flags) if !flags.contains(OpFlags::K) => Ok(flags),
+            _ => Err(serde::de::Error::custom(format_args!(
+                "invalid op flags: {:#04x}",
+                bits
+            ))),
+        }
+    }
+}
+
+/// Security parameter. Choice of 128 or 256 bits. For a non-standard security level, see
+/// [`Strobe::new_with_security`].
+#[derive(Clone, Copy, Debug)]
 #[repr(usize)]
 pub enum SecParam {
     B128 = 128,
     B256 = 256,
 }
 
-/// An empty struct that just indicates that MAC verification failed
+impl SecParam {
+    /// Returns the number of bits of security this parameter represents
+    pub const fn bits(&self) -> usize {
+        *self as usize
+    }
+
+    /// Returns the `R` parameter in the Strobe spec for this security level, when used with a
+    /// permutation of `perm_block_bytes` bytes. This is `perm_block_bytes - bits/4 - 2`. Returns
+    /// `None` if the rate does not lie in `[1, 254)`.
+    ///
+    /// This crate uses Keccak-f\[1600\], whose block is 200 bytes.
+    pub const fn rate(&self, perm_block_bytes: usize) -> Option<usize> {
+        rate_for_bits(self.bits(), perm_block_bytes)
+    }
+}
+
+/// Returns the rate of a security level of `bits` bits with a permutation of `perm_block_bytes`
+/// bytes, or `None` if `bits` is not a multiple of 4 that is at least 100, or if the rate does not
+/// lie in `[1, 254)`.
+const fn rate_for_bits(bits: usize, perm_block_bytes: usize) -> Option<usize> {
+    // The capacity is 2*bits bits, which has to be a whole number of bytes. Also, the version
+    // string has exactly 3 characters for the security level, and the spec doesn't say how to pad
+    // shorter ones, so anything under 100 bits is rejected.
+    if bits < 100 || bits % 4 != 0 {
+        return None;
+    }
+
+    // The rate must lie in [1, 254), so that R+2 fits in the byte that encodes it at init
+    match perm_block_bytes.checked_sub(bits / 4 + 2) {
+        Some(rate) if rate >= 1 && rate < 254 => Some(rate),
+        _ => None,
+    }
+}
+
+// SecParams serialize as their bit count, matching the format of the KAT vectors. Invalid bit
+// counts are rejected.
+#[cfg(feature = "serialize_secret_state")]
+impl Serialize for SecParam {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.bits().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serialize_secret_state")]
+impl<'de> Deserialize<'de> for SecParam {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bits = usize::deserialize(deserializer)?;
+        match bits {
+            128 => Ok(SecParam::B128),
+            256 => Ok(SecParam::B256),
+            _ => Err(serde::de::Error::custom(format_args!(
+                "invalid security parameter: {}",
+                bits
+            ))),
+        }
+    }
+}
+
+// A Strobe's security level serializes as its bit count, like SecParam. Invalid bit counts are
+// rejected.
+#[cfg(feature = "serialize_secret_state")]
+fn deserialize_sec_bits<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<usize, D::Error> {
+    let bits = usize::deserialize(deserializer)?;
+    match rate_for_bits(bits, KECCAK_BLOCK_BYTES) {
+        Some(_) => Ok(bits),
+        None => Err(serde::de::Error::custom(format_args!(
+            "invalid security parameter: {}",
+            bits
+        ))),
+    }
+}
+
+/// An empty struct that just indicates that MAC verification failed. This is marked
+/// `#[non_exhaustive]` so that it can carry more information in the future without a breaking
+/// change.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
 pub struct AuthError;
 
 impl core::fmt::Display for AuthError {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        f.write_str("MAC verification failed")
+        f.write_str("STROBE MAC verification failed")
+    }
+}
+
+/// The errors that fallible `Strobe` operations can return. An `AuthError` converts into
+/// `StrobeError::MacMismatch`, so `recv_mac` results can be propagated with `?`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StrobeError {
+    /// MAC verification failed
+    MacMismatch,
+    /// The given state is not a state that a `Strobe` could ever be in
+    InvalidState,
+    /// The given security parameter does not yield a valid rate
+    InvalidSecurityParam,
+    /// The direction (sender or receiver) was already set, either explicitly or by a transport
+    /// operation, and cannot be changed
+    DirectionLocked,
+    /// The requested MAC is shorter than [`MIN_MAC_LEN`]
+    MacTooShort,
+    /// The requested rate does not fit in the Keccak state
+    InvalidRate,
+    /// The given output buffer is too small
+    BufferTooSmall,
+    /// Two `Strobe`s that were expected to have the same security parameter and rate do not
+    SecParamMismatch,
+    /// The operation requires a keyed state, but no `key` operation has been run
+    Unkeyed,
+    /// The given buffer is not the expected length
+    LengthMismatch,
+    /// The serialized state's checksum does not match, i.e., it was corrupted
+    CorruptState,
+    /// The runtime self-test failed on the given test vector, at the given operation
+    SelfTestFailed {
+        vector: &'static str,
+        operation: usize,
+    },
+}
+
+impl core::fmt::Display for StrobeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            StrobeError::MacMismatch => f.write_str("MAC verification failed"),
+            StrobeError::InvalidState => f.write_str("invalid Strobe state"),
+            StrobeError::InvalidSecurityParam => f.write_str(
+                "invalid security parameter: must be a multiple of 4 between 100 and 788",
+            ),
+            StrobeError::DirectionLocked => f.write_str("direction is already set"),
+            StrobeError::BufferTooSmall => f.write_str("output buffer is too small"),
+            StrobeError::SecParamMismatch => f.write_str("security parameters do not match"),
+            StrobeError::Unkeyed => f.write_str("state has not been keyed"),
+            StrobeError::LengthMismatch => f.write_str("buffer is not the expected length"),
+            StrobeError::CorruptState => f.write_str("serialized state checksum mismatch"),
+            StrobeError::InvalidRate => f.write_str("invalid rate: must be between 1 and 198"),
+            StrobeError::MacTooShort => {
+                write!(
+                    f,
+                    "MAC length is less than the minimum of {} bytes",
+                    MIN_MAC_LEN
+                )
+            }
+            StrobeError::SelfTestFailed { vector, operation } => write!(
+                f,
+                "self-test failed on the {} test vector at operation {}",
+                vector, operation
+            ),
+        }
+    }
+}
+
+impl From<AuthError> for StrobeError {
+    fn from(_: AuthError) -> StrobeError {
+        StrobeError::MacMismatch
     }
 }
 
@@ -71,7 +321,7 @@ impl core::fmt::Display for AuthError {
 ///
 /// * `data` - The input data to the operation.
 /// * `more` - For streaming purposes. Specifies whether you're trying to add more input / get more
-///            output to/from the previous operation. For example:
+///   output to/from the previous operation. For example:
 ///
 /// ```rust
 /// # extern crate strobe_rs;
@@ -94,7 +344,8 @@ impl core::fmt::Display for AuthError {
 ///
 /// **NOTE:** If you try to set the `more` flag for an operation that is not preceded by the same
 /// operation (e.g., if you try `ad` followed by `send_enc` with `more=true`), then **the function
-/// will panic**, since that is an invalid use of the `more` flag.
+/// will panic**, since that is an invalid use of the `more` flag. This check is a single
+/// comparison, and is always enabled, including in release builds.
 ///
 /// Finally, `ratchet` and `meta_ratchet` take a `usize` argument instead of bytes. These functions
 /// are individually commented below.
@@ -103,9 +354,14 @@ impl core::fmt::Display for AuthError {
 pub struct Strobe {
     /// Internal Keccak state
     pub(crate) st: AlignedKeccakState,
-    /// Security parameter (128 or 256)
+    /// Security level, in bits. This is usually 128 or 256, but can be any level accepted by
+    /// `new_with_security`.
     #[zeroize(skip)]
-    sec: SecParam,
+    #[cfg_attr(
+        feature = "serialize_secret_state",
+        serde(deserialize_with = "deserialize_sec_bits")
+    )]
+    sec: usize,
     /// This is the `R` parameter in the Strobe spec
     rate: usize,
     /// Index into `st`
@@ -117,6 +373,85 @@ pub struct Strobe {
     /// The last operation performed. This is to verify that the `more` flag is only used across
     /// identical operations.
     prev_flags: Option<OpFlags>,
+    /// The total number of bytes processed by all operations so far. States serialized before this
+    /// field existed deserialize with a count of 0.
+    #[cfg_attr(feature = "serialize_secret_state", serde(default))]
+    bytes_processed: u64,
+    /// Whether a `key` or `meta_key` operation has ever been run. States serialized before this
+    /// field existed deserialize as unkeyed.
+    #[cfg_attr(feature = "serialize_secret_state", serde(default))]
+    keyed: bool,
+    /// The number of times the permutation has run since this `Strobe` was made or deserialized.
+    /// This is a diagnostic, not protocol state, so it is not serialized.
+    #[cfg_attr(feature = "serialize_secret_state", serde(skip))]
+    perm_count: u64,
+}
+
+// This defines an operation and meta-operation that mix an integer into the state as associated
+// data, with the given encoding
+macro_rules! def_ad_int {
+    ($name:ident, $meta_name:ident, $ty:ty, $to_bytes:ident, $doc_str:expr) => {
+        #[doc = $doc_str]
+        pub fn $name(&mut self, x: $ty) {
+            self.ad(&x.$to_bytes(), /* more */ false);
+        }
+
+        #[doc = $doc_str]
+        pub fn $meta_name(&mut self, x: $ty) {
+            self.meta_ad(&x.$to_bytes(), /* more */ false);
+        }
+    };
+}
+
+/// Compares every field of two `Strobe`s, including the secret state. This is meant for asserting
+/// equality in tests.
+///
+/// **SECURITY NOTE**: This comparison is variable-time. It MUST NOT be used on secret-dependent
+/// paths. Use `send_mac`/`recv_mac` to check that two parties' transcripts agree.
+#[cfg(feature = "test-util")]
+impl PartialEq for Strobe {
+    fn eq(&self, other: &Strobe) -> bool {
+        self.st.0[..] == other.st.0[..]
+            && self.sec == other.sec
+            && self.rate == other.rate
+            && self.pos == other.pos
+            && self.pos_begin == other.pos_begin
+            && self.is_receiver == other.is_receiver
+            && self.prev_flags == other.prev_flags
+            && self.bytes_processed == other.bytes_processed
+            && self.keyed == other.keyed
+    }
+}
+
+#[cfg(feature = "test-util")]
+impl Eq for Strobe {}
+
+// Strobe state is secret, so we never print it. Everything else is fair game.
+impl core::fmt::Debug for Strobe {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        // Prints as `[redacted; 200]`
+        struct Redacted(usize);
+        impl core::fmt::Debug for Redacted {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                write!(f, "[redacted; {}]", self.0)
+            }
+        }
+
+        let version_str = self.version_str();
+        f.debug_struct("Strobe")
+            .field("version_str", &core::str::from_utf8(&version_str).unwrap())
+            .field("st", &Redacted(self.st.0.len()))
+            .field("sec", &self.sec)
+            .field("rate", &self.rate)
+            .field("pos", &self.pos)
+            .field("pos_begin", &self.pos_begin)
+            .field("is_receiver", &self.is_receiver)
+            .field("prev_flags", &self.prev_flags)
+            .field("bytes_processed", &self.bytes_processed)
+            .field("keyed", &self.keyed)
+            .field("perm_count", &self.perm_count)
+            .finish()
+    }
 }
 
 // This defines an operation and meta-operation that mutates its input
@@ -140,26 +475,173 @@ macro_rules! def_op_mut {
 macro_rules! def_op_no_mut {
     ($name:ident, $meta_name:ident, $flags:expr, $doc_str:expr) => {
         #[doc = $doc_str]
-        pub fn $name(&mut self, data: &[u8], more: bool) {
+        pub fn $name(&mut self, data: impl AsRef<[u8]>, more: bool) {
             let flags = $flags;
-            self.operate_no_mutate(flags, data, more);
+            self.operate_no_mutate(flags, data.as_ref(), more);
         }
 
         #[doc = $doc_str]
-        pub fn $meta_name(&mut self, data: &[u8], more: bool) {
+        pub fn $meta_name(&mut self, data: impl AsRef<[u8]>, more: bool) {
             let flags = $flags | OpFlags::M;
-            self.operate_no_mutate(flags, data, more);
+            self.operate_no_mutate(flags, data.as_ref(), more);
         }
     };
 }
 
+// The byte-at-a-time duplex loops are the bottleneck on large inputs. These helpers do the same
+// work a whole 64-bit lane at a time. Since every op here is built out of XORs and copies, the
+// result doesn't depend on endianness, and the output is bit-identical to the bytewise loop.
+
+/// Number of bytes in a Keccak lane
+const LANE_LEN: usize = 8;
+
+/// Returns how many leading bytes of a run starting at state index `pos` and of length `len` must
+/// be handled bytewise before we reach a lane boundary
+fn lane_head_len(pos: usize, len: usize) -> usize {
+    core::cmp::min((LANE_LEN - pos % LANE_LEN) % LANE_LEN, len)
+}
+
+/// Applies `op` to `st` and `data` lane by lane, where `st` begins at state index `pos`. Bytes
+/// before the first lane boundary and after the last full lane are processed one at a time.
+fn lanewise(pos: usize, st: &mut [u8], data: &[u8], op: impl Fn(&mut u64, u64)) {
+    debug_assert_eq!(st.len(), data.len());
+    let head = lane_head_len(pos, data.len());
+    let (st_head, st_body) = st.split_at_mut(head);
+    let (data_head, data_body) = data.split_at(head);
+
+    let mut st_lanes = st_body.chunks_exact_mut(LANE_LEN);
+    let mut data_lanes = data_body.chunks_exact(LANE_LEN);
+    for (s, d) in (&mut st_lanes).zip(&mut data_lanes) {
+        let mut s_lane = u64::from_ne_bytes(s.try_into().unwrap());
+        op(&mut s_lane, u64::from_ne_bytes(d.try_into().unwrap()));
+        s.copy_from_slice(&s_lane.to_ne_bytes());
+    }
+
+    let st_tail = st_head.iter_mut().chain(st_lanes.into_remainder());
+    let data_tail = data_head.iter().chain(data_lanes.remainder());
+    for (s, d) in st_tail.zip(data_tail) {
+        let mut s_wide = u64::from(*s);
+        op(&mut s_wide, u64::from(*d));
+        *s = s_wide as u8;
+    }
+}
+
+/// Like `lanewise`, but `op` may also mutate `data`
+fn lanewise_mut(pos: usize, st: &mut [u8], data: &mut [u8], op: impl Fn(&mut u64, &mut u64)) {
+    debug_assert_eq!(st.len(), data.len());
+    let head = lane_head_len(pos, data.len());
+    let (st_head, st_body) = st.split_at_mut(head);
+    let (data_head, data_body) = data.split_at_mut(head);
+
+    let mut st_lanes = st_body.chunks_exact_mut(LANE_LEN);
+    let mut data_lanes = data_body.chunks_exact_mut(LANE_LEN);
+    for (s, d) in (&mut st_lanes).zip(&mut data_lanes) {
+        let mut s_lane = u64::from_ne_bytes((&*s).try_into().unwrap());
+        let mut d_lane = u64::from_ne_bytes((&*d).try_into().unwrap());
+        op(&mut s_lane, &mut d_lane);
+        s.copy_from_slice(&s_lane.to_ne_bytes());
+        d.copy_from_slice(&d_lane.to_ne_bytes());
+    }
+
+    let st_tail = st_head.iter_mut().chain(st_lanes.into_remainder());
+    let data_tail = data_head.iter_mut().chain(data_lanes.into_remainder());
+    for (s, d) in st_tail.zip(data_tail) {
+        let (mut s_wide, mut d_wide) = (u64::from(*s), u64::from(*d));
+        op(&mut s_wide, &mut d_wide);
+        *s = s_wide as u8;
+        *d = d_wide as u8;
+    }
+}
+
 impl Strobe {
     /// Makes a new `Strobe` object with a given protocol byte string and security parameter.
+    ///
+    /// Panics if `sec` is invalid for the permutation. This never happens with Keccak-f\[1600\],
+    /// but see [`Strobe::try_new`] for a non-panicking alternative.
     pub fn new(proto: &[u8], sec: SecParam) -> Strobe {
-        let rate = KECCAK_BLOCK_SIZE * 8 - (sec as usize) / 4 - 2;
-        assert!(rate >= 1);
-        assert!(rate < 254);
+        Strobe::try_new(proto, sec).expect("invalid security parameter")
+    }
+
+    /// Makes a new `Strobe` object with a given protocol byte string and security parameter.
+    /// This is the same as [`Strobe::new`], but returns `StrobeError::InvalidSecurityParam`
+    /// instead of panicking if `sec` is invalid.
+    pub fn try_new(proto: &[u8], sec: SecParam) -> Result<Strobe, StrobeError> {
+        let rate = sec
+            .rate(KECCAK_BLOCK_BYTES)
+            .ok_or(StrobeError::InvalidSecurityParam)?;
+        Ok(Strobe::init(proto, sec.bits(), rate))
+    }
+
+    /// Makes a new `Strobe` object with a given protocol byte string and security parameter, then
+    /// mixes in a domain-separating `salt`. This is exactly `new(proto, sec)` followed by
+    /// `meta_ad(salt, false)`.
+    ///
+    /// Panics under the same conditions as [`Strobe::new`].
+    pub fn new_with_salt(proto: &[u8], salt: &[u8], sec: SecParam) -> Strobe {
+        let mut s = Strobe::new(proto, sec);
+        s.meta_ad(salt, false);
+        s
+    }
+
+    /// Makes a new `Strobe` object with protocol byte string `base`, then mixes in `context`,
+    /// prefixed by its length. This runs `new(base, sec)`, then `meta_ad` on the length of
+    /// `context`, encoded as an 8-byte little-endian integer, then `meta_ad(context, false)`.
+    ///
+    /// Unlike concatenating `base` and `context` into a single protocol string, this is
+    /// unambiguous, e.g., `("a", "bc")` and `("ab", "c")` give different states.
+    ///
+    /// Panics under the same conditions as [`Strobe::new`].
+    pub fn new_contextual(base: &[u8], context: &[u8], sec: SecParam) -> Strobe {
+        let mut s = Strobe::new(base, sec);
+        s.meta_ad(framed_len(context), false);
+        s.meta_ad(context, false);
+        s
+    }
+
+    /// Resets this `Strobe` to the state of `Strobe::new(proto, sec)`, e.g., to reuse a pooled
+    /// `Strobe` at a different security level. The old state is zeroized, and everything that
+    /// depends on the security level, including the rate, is recomputed.
+    ///
+    /// Panics under the same conditions as [`Strobe::new`].
+    pub fn reinit(&mut self, proto: &[u8], sec: SecParam) {
+        // Assigning drops the old value, which zeroizes it
+        *self = Strobe::new(proto, sec);
+    }
+
+    /// Makes a new `Strobe` object with a given protocol byte string and a security level of
+    /// `bits` bits. The security level must be a multiple of 4 that is at least 100, and leave a
+    /// rate of at least 1 byte, i.e., `bits` can be at most 788. Otherwise, this returns
+    /// `StrobeError::InvalidSecurityParam`.
+    pub fn new_with_security(proto: &[u8], bits: usize) -> Result<Strobe, StrobeError> {
+        // rate = 200 - bits/4 - 2, rejecting levels that aren't a whole number of bytes of
+        // capacity, or that leave no room for the rate
+        let rate =
+            rate_for_bits(bits, KECCAK_BLOCK_BYTES).ok_or(StrobeError::InvalidSecurityParam)?;
+        Ok(Strobe::init(proto, bits, rate))
+    }
 
+    /// Makes a new `Strobe` object with a given protocol byte string and security parameter, but
+    /// with a rate of `rate` bytes rather than the one the STROBE spec derives from `sec`. A larger
+    /// rate means fewer permutation calls, and so higher throughput, at the cost of security
+    /// margin: the capacity is `200 - rate - 2` bytes, regardless of what `sec` says.
+    ///
+    /// **WARNING:** This is not standard STROBE. A `Strobe` made this way does not interoperate
+    /// with any other STROBE implementation, nor with a `Strobe` made by [`Strobe::new`]. Only use
+    /// this for internal protocols where both sides use this crate with the same rate.
+    /// Also, the raw parts returned by [`Strobe::into_raw_parts`] do not include the rate, so use
+    /// `to_bytes` or `serde` to checkpoint such a `Strobe` instead.
+    ///
+    /// Returns `StrobeError::InvalidRate` unless `1 <= rate <= 198`, i.e., unless the rate and its
+    /// 2 padding bytes fit in the state.
+    pub fn new_with_rate(proto: &[u8], sec: SecParam, rate: usize) -> Result<Strobe, StrobeError> {
+        if !is_valid_rate(rate) {
+            return Err(StrobeError::InvalidRate);
+        }
+        Ok(Strobe::init(proto, sec.bits(), rate))
+    }
+
+    // Makes a Strobe with the given parameters, which are assumed to be valid
+    fn init(proto: &[u8], sec: usize, rate: usize) -> Strobe {
         // Initialize state: st = F([0x01, R+2, 0x01, 0x00, 0x01, 0x60] + b"STROBEvX.Y.Z")
         let mut st_buf = [0u8; KECCAK_BLOCK_SIZE * 8];
         st_buf[0..6].copy_from_slice(&[0x01, (rate as u8) + 2, 0x01, 0x00, 0x01, 0x60]);
@@ -177,6 +659,9 @@ impl Strobe {
             pos_begin: 0,
             is_receiver: None,
             prev_flags: None,
+            bytes_processed: 0,
+            keyed: false,
+            perm_count: 0,
         };
 
         // Mix the protocol into the state
@@ -185,22 +670,411 @@ impl Strobe {
         strobe
     }
 
+    /// Returns the total number of bytes processed by all operations, including meta-operations,
+    /// since this `Strobe` was made. For `ratchet`, this counts the number of bytes zeroed. This is
+    /// useful for enforcing policies such as rekeying after some number of bytes.
+    ///
+    /// The count saturates at `u64::MAX`. It is preserved by serialization, but starts over at 0
+    /// for a `Strobe` made by [`Strobe::from_raw_parts`].
+    pub fn bytes_processed(&self) -> u64 {
+        self.bytes_processed
+    }
+
+    /// Returns whether a `key` or `meta_key` operation has ever been run on this `Strobe`. An
+    /// unkeyed state is public, so anything "encrypted" under it is trivially decryptable. See
+    /// [`Strobe::send_enc_checked`].
+    ///
+    /// This is preserved by serialization, but is `false` for a `Strobe` made by
+    /// [`Strobe::from_raw_parts`], since the raw parts don't record it.
+    pub fn is_keyed(&self) -> bool {
+        self.keyed
+    }
+
+    /// Absorbs `data` directly into the sponge, i.e., XORs it into the state at the current
+    /// position, running the permutation at every block boundary.
+    ///
+    /// **WARNING:** This is a research tool. It does NOT run `begin_op`, so nothing records where
+    /// the data begins or what kind of operation it is, and the result is not a standard STROBE
+    /// transcript. This bypasses all of STROBE's domain separation, so it MUST NOT be used in
+    /// production protocols. It is not covered by any stability guarantees.
+    #[cfg(feature = "unstable-sponge")]
+    pub fn sponge_absorb(&mut self, data: &[u8]) {
+        self.absorb(data);
+    }
+
+    /// Squeezes `out.len()` bytes directly out of the sponge, i.e., copies them from the state at
+    /// the current position and zeroes them in the state, running the permutation at every block
+    /// boundary.
+    ///
+    /// **WARNING:** The same caveats as for [`Strobe::sponge_absorb`] apply. In particular, this
+    /// does NOT run `begin_op`, so its output is not that of any STROBE operation.
+    #[cfg(feature = "unstable-sponge")]
+    pub fn sponge_squeeze(&mut self, out: &mut [u8]) {
+        self.squeeze(out);
+    }
+
+    /// Returns a reference to the raw Keccak state, without copying it. This is meant for custom
+    /// persistence or attestation schemes that need the state bytes but not the rest of `serde`.
+    ///
+    /// **SECURITY NOTE:** The state is security sensitive. Treat it as you would a secret key.
+    /// It also does not include the position, direction, etc., so it is not enough to resume a
+    /// session on its own. For that, use [`Strobe::into_raw_parts`].
+    #[cfg(feature = "expose-state")]
+    pub fn state_bytes(&self) -> &[u8; 8 * KECCAK_BLOCK_SIZE] {
+        &self.st.0
+    }
+
+    /// Returns the number of times the Keccak permutation has run since this `Strobe` was made,
+    /// not counting the one at initialization. This is useful for measuring the cost of different
+    /// ways of structuring a transcript, e.g., batching `ad` calls.
+    ///
+    /// This is a diagnostic, not protocol state. It is ignored by the `test-util` equality
+    /// comparison, and starts over at 0 when a `Strobe` is deserialized or rebuilt from parts.
+    pub fn permutation_count(&self) -> u64 {
+        self.perm_count
+    }
+
+    /// Returns the number of bytes left in the current block, i.e., how many more bytes of data
+    /// can be processed before the permutation next runs. Processing exactly this many bytes lands
+    /// on the block boundary and triggers the permutation. Note that beginning a new operation
+    /// first absorbs 2 bytes of framing, and always runs the permutation for ops with the `C`
+    /// flag, e.g., `key`, `prf`, and `send_enc`.
+    ///
+    /// This reveals only the position in the block, which depends only on the lengths of the
+    /// operations so far, and not on any secret.
+    pub fn rate_remaining(&self) -> usize {
+        self.rate - self.pos
+    }
+
+    // Adds to the running count of bytes processed
+    fn count_bytes(&mut self, len: usize) {
+        self.bytes_processed = self.bytes_processed.saturating_add(len as u64);
+    }
+
+    /// Returns whether this `Strobe` is the receiver (`Some(true)`) or the sender
+    /// (`Some(false)`), or `None` if the direction has not been set yet. The direction is set by
+    /// `set_receiver`, or by the first transport (`send_*`/`recv_*`) operation.
+    pub fn is_receiver(&self) -> Option<bool> {
+        self.is_receiver
+    }
+
+    /// Explicitly sets whether this `Strobe` is the receiver or the sender, instead of letting the
+    /// first transport operation decide. This is useful when resuming a session in which the other
+    /// side performed the first transport operation.
+    ///
+    /// Returns `StrobeError::DirectionLocked` if the direction has already been set.
+    pub fn set_receiver(&mut self, is_receiver: bool) -> Result<(), StrobeError> {
+        if self.is_receiver.is_some() {
+            return Err(StrobeError::DirectionLocked);
+        }
+
+        self.is_receiver = Some(is_receiver);
+        Ok(())
+    }
+
+    /// Sets `self` to `other` if `choice` is set, and leaves it unchanged otherwise, without
+    /// branching on `choice`. The Keccak state, positions, and byte count are selected in constant
+    /// time. The direction, previous operation, and keyed flag are selected in constant time as
+    /// small integers, but converting them back is not guaranteed to be branch-free.
+    ///
+    /// Both `Strobe`s must have the same security parameter and rate. Otherwise, this returns
+    /// `StrobeError::SecParamMismatch` and leaves `self` unchanged.
+    #[cfg(feature = "subtle")]
+    pub fn conditional_assign(
+        &mut self,
+        other: &Strobe,
+        choice: subtle::Choice,
+    ) -> Result<(), StrobeError> {
+        if self.sec != other.sec || self.rate != other.rate {
+            return Err(StrobeError::SecParamMismatch);
+        }
+
+        for (a, b) in self.st.0.iter_mut().zip(other.st.0.iter()) {
+            a.conditional_assign(b, choice);
+        }
+        let select_usize =
+            |a: usize, b: usize| u64::conditional_select(&(a as u64), &(b as u64), choice) as usize;
+        self.pos = select_usize(self.pos, other.pos);
+        self.pos_begin = select_usize(self.pos_begin, other.pos_begin);
+        self.bytes_processed
+            .conditional_assign(&other.bytes_processed, choice);
+
+        // Encode the Options as integers, select, then decode. The encoding of prev_flags is its
+        // bits, or 0xff for None.
+        let encode_receiver = |r: Option<bool>| match r {
+            None => 0u8,
+            Some(false) => 1,
+            Some(true) => 2,
+        };
+        let encode_flags = |f: Option<OpFlags>| f.map_or(0xff, |f| f.bits());
+        let is_receiver = u8::conditional_select(
+            &encode_receiver(self.is_receiver),
+            &encode_receiver(other.is_receiver),
+            choice,
+        );
+        let prev_flags = u8::conditional_select(
+            &encode_flags(self.prev_flags),
+            &encode_flags(other.prev_flags),
+            choice,
+        );
+        self.is_receiver = match is_receiver {
+            0 => None,
+            1 => Some(false),
+            _ => Some(true),
+        };
+        self.prev_flags = OpFlags::from_bits(prev_flags);
+        self.keyed = u8::conditional_select(&(self.keyed as u8), &(other.keyed as u8), choice) != 0;
+
+        Ok(())
+    }
+
+    /// Reconstructs a `Strobe` from the parts returned by [`Strobe::into_raw_parts`]. This is a
+    /// serde-free way of checkpointing a state, e.g., to resume many sessions from a single keyed
+    /// state.
+    ///
+    /// Returns `StrobeError::InvalidState` if the positions are not ones a `Strobe` can be in,
+    /// i.e., unless `pos_begin <= pos < rate`. Since the previous operation is not part of the raw
+    /// parts, the first operation on the returned `Strobe` cannot set `more`.
+    ///
+    /// **SECURITY NOTE:** `st` is security sensitive. Treat it as you would a secret key.
+    pub fn from_raw_parts(
+        st: [u8; 8 * KECCAK_BLOCK_SIZE],
+        sec: SecParam,
+        pos: usize,
+        pos_begin: usize,
+        is_receiver: Option<bool>,
+    ) -> Result<Strobe, StrobeError> {
+        let rate = sec
+            .rate(KECCAK_BLOCK_BYTES)
+            .ok_or(StrobeError::InvalidSecurityParam)?;
+        Strobe::from_parts(st, sec.bits(), rate, pos, pos_begin, is_receiver)
+    }
+
+    // Makes a Strobe from its parts, checking that they are consistent
+    fn from_parts(
+        st: [u8; 8 * KECCAK_BLOCK_SIZE],
+        sec: usize,
+        rate: usize,
+        pos: usize,
+        pos_begin: usize,
+        is_receiver: Option<bool>,
+    ) -> Result<Strobe, StrobeError> {
+        rate_for_bits(sec, KECCAK_BLOCK_BYTES).ok_or(StrobeError::InvalidSecurityParam)?;
+        if !is_valid_rate(rate) || pos >= rate || pos_begin > pos {
+            return Err(StrobeError::InvalidState);
+        }
+
+        Ok(Strobe {
+            st: AlignedKeccakState(st),
+            sec,
+            rate,
+            pos,
+            pos_begin,
+            is_receiver,
+            prev_flags: None,
+            bytes_processed: 0,
+            keyed: false,
+            perm_count: 0,
+        })
+    }
+
+    /// Decomposes this `Strobe` into its internal state, security parameter, position, beginning
+    /// position, and direction. Use [`Strobe::from_raw_parts`] to reconstruct it.
+    ///
+    /// Panics if this `Strobe` was made by [`Strobe::new_with_security`] with a level other than
+    /// 128 or 256 bits, since that level has no `SecParam`. Use `to_bytes` or `serde` to checkpoint
+    /// such a `Strobe` instead.
+    ///
+    /// **SECURITY NOTE:** The returned state is security sensitive. Treat it as you would a secret
+    /// key.
+    pub fn into_raw_parts(
+        self,
+    ) -> (
+        [u8; 8 * KECCAK_BLOCK_SIZE],
+        SecParam,
+        usize,
+        usize,
+        Option<bool>,
+    ) {
+        let sec = match self.sec {
+            128 => SecParam::B128,
+            256 => SecParam::B256,
+            _ => panic!("non-standard security level has no SecParam"),
+        };
+
+        // We can't move out of a type that impls Drop, so copy everything out. self is zeroized
+        // when it goes out of scope.
+        (self.st.0, sec, self.pos, self.pos_begin, self.is_receiver)
+    }
+
+    /// Serializes the full state of this `Strobe` into a compact binary encoding. Use
+    /// [`Strobe::from_bytes`] to deserialize it.
+    ///
+    /// The returned buffer is wiped when it is dropped.
+    ///
+    /// **SECURITY NOTE:** The output is security sensitive. Treat it as you would a secret key.
+    /// The buffer is only wiped in memory. Callers must still wipe any copies they write elsewhere,
+    /// e.g., to disk.
+    #[cfg(all(feature = "serialize_secret_state", feature = "alloc"))]
+    pub fn to_bytes(&self) -> zeroize::Zeroizing<Vec<u8>> {
+        // The format is
+        //     st || sec_bits (u16 LE) || rate (u8) || pos (u8) || pos_begin (u8)
+        //        || is_receiver (u8) || has_prev_flags (u8) || prev_flags (u8)
+        //        || bytes_processed (u64 LE) || keyed (u8) || checksum (u32 LE)
+        // where is_receiver is 0 for None, 1 for Some(false), and 2 for Some(true). The rate and
+        // positions fit in a byte because the rate is less than the state size. The rate is
+        // stored separately from the security parameter, since it may be custom. The checksum is
+        // the CRC-32 of everything before it.
+        let mut out = zeroize::Zeroizing::new(Vec::with_capacity(SERIALIZED_STATE_LEN));
+        out.extend_from_slice(&self.st.0);
+        out.extend_from_slice(&(self.sec as u16).to_le_bytes());
+        out.push(self.rate as u8);
+        out.push(self.pos as u8);
+        out.push(self.pos_begin as u8);
+        out.push(match self.is_receiver {
+            None => 0,
+            Some(false) => 1,
+            Some(true) => 2,
+        });
+        match self.prev_flags {
+            None => out.extend_from_slice(&[0, 0]),
+            Some(flags) => out.extend_from_slice(&[1, flags.bits()]),
+        }
+        out.extend_from_slice(&self.bytes_processed.to_le_bytes());
+        out.push(self.keyed as u8);
+        let checksum = crc32(&out);
+        out.extend_from_slice(&checksum.to_le_bytes());
+
+        out
+    }
+
+    /// Deserializes a `Strobe` from the output of [`Strobe::to_bytes`].
+    ///
+    /// Returns `StrobeError::CorruptState` if the checksum doesn't match, e.g., because `bytes` was
+    /// corrupted on disk or in transit. The checksum only detects accidental corruption. It is not
+    /// cryptographic, and does nothing against deliberate tampering. Otherwise, returns
+    /// `StrobeError::InvalidSecurityParam` if the encoded security parameter is invalid, and
+    /// `StrobeError::InvalidState` if `bytes` is otherwise malformed.
+    #[cfg(all(feature = "serialize_secret_state", feature = "alloc"))]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Strobe, StrobeError> {
+        if bytes.len() != SERIALIZED_STATE_LEN {
+            return Err(StrobeError::InvalidState);
+        }
+        let (payload, checksum) = bytes.split_at(SERIALIZED_STATE_LEN - 4);
+        if crc32(payload).to_le_bytes() != checksum {
+            return Err(StrobeError::CorruptState);
+        }
+        let (st_bytes, rest) = payload.split_at(8 * KECCAK_BLOCK_SIZE);
+
+        let mut st = [0u8; 8 * KECCAK_BLOCK_SIZE];
+        st.copy_from_slice(st_bytes);
+        let sec = u16::from_le_bytes([rest[0], rest[1]]) as usize;
+        let (rate, pos, pos_begin) = (rest[2] as usize, rest[3] as usize, rest[4] as usize);
+        let is_receiver = match rest[5] {
+            0 => None,
+            1 => Some(false),
+            2 => Some(true),
+            _ => return Err(StrobeError::InvalidState),
+        };
+        let prev_flags = match (rest[6], OpFlags::from_bits(rest[7])) {
+            (0, _) => None,
+            (1, Some(flags)) if !flags.contains(OpFlags::K) => Some(flags),
+            _ => return Err(StrobeError::InvalidState),
+        };
+        let keyed = match rest[16] {
+            0 => false,
+            1 => true,
+            _ => return Err(StrobeError::InvalidState),
+        };
+
+        let mut s = Strobe::from_parts(st, sec, rate, pos, pos_begin, is_receiver);
+        st.zeroize();
+        if let Ok(s) = s.as_mut() {
+            s.prev_flags = prev_flags;
+            s.bytes_processed = u64::from_le_bytes(rest[8..16].try_into().unwrap());
+            s.keyed = keyed;
+        }
+        s
+    }
+
     /// Returns a bytestring of the form `Strobe-Keccak-SEC/B-vVER` where `SEC` is the bits of
-    /// security (128 or 256), `B` is the block size (in bits) of the Keccak permutation function,
-    /// and `VER` is the protocol version.
+    /// security (e.g., 128 or 256), `B` is the block size (in bits) of the Keccak permutation
+    /// function, and `VER` is the protocol version.
     pub fn version_str(&self) -> [u8; TEMPLATE_VERSION_STR.len()] {
         let mut buf = TEMPLATE_VERSION_STR;
 
-        match self.sec {
-            SecParam::B128 => buf[14..17].copy_from_slice(b"128"),
-            SecParam::B256 => buf[14..17].copy_from_slice(b"256"),
-        }
+        // Valid security levels are exactly 3 digits long
+        let bits = self.sec;
+        debug_assert!((100..1000).contains(&bits));
+        buf[14] = b'0' + (bits / 100) as u8;
+        buf[15] = b'0' + (bits / 10 % 10) as u8;
+        buf[16] = b'0' + (bits % 10) as u8;
         buf[18..22].copy_from_slice(KECCAK_BLOCK_BITLEN_STR);
         buf[24..29].copy_from_slice(STROBE_VERSION);
 
         buf
     }
 
+    /// Writes the version string, as returned by [`Strobe::version_str`], into `buf`, replacing
+    /// its contents. This is for targets without an allocator that want the version string as a
+    /// string type.
+    ///
+    /// Returns `StrobeError::BufferTooSmall`, leaving `buf` empty, if `N` is less than 29.
+    #[cfg(feature = "heapless")]
+    pub fn version_str_into<const N: usize>(
+        &self,
+        buf: &mut heapless::String<N>,
+    ) -> Result<(), StrobeError> {
+        let version_str = self.version_str();
+        buf.clear();
+        // The version string is always ASCII
+        buf.push_str(core::str::from_utf8(&version_str).unwrap())
+            .map_err(|_| StrobeError::BufferTooSmall)
+    }
+
+    /// Writes the version string, as returned by [`Strobe::version_str`], into the start of `buf`,
+    /// and returns it as a `&str` borrowed from `buf`. This needs neither an allocator nor the
+    /// `heapless` feature.
+    ///
+    /// Returns `core::fmt::Error`, leaving `buf` unmodified, if `buf` is shorter than 29 bytes.
+    pub fn version_str_into_slice<'a>(
+        &self,
+        buf: &'a mut [u8],
+    ) -> Result<&'a str, core::fmt::Error> {
+        let version_str = self.version_str();
+        let out = buf.get_mut(..version_str.len()).ok_or(core::fmt::Error)?;
+        out.copy_from_slice(&version_str);
+        // The version string is always ASCII
+        Ok(core::str::from_utf8(out).unwrap())
+    }
+
+    /// Commits the transcript to the STROBE version and parameters, by running `meta_ad` on
+    /// [`Strobe::version_str`]. Peers that disagree on the version string then disagree on every
+    /// subsequent MAC, so an incompatibility shows up as a clean `recv_mac` failure.
+    ///
+    /// This is optional, and changes the transcript, so both peers must call it at the same point.
+    pub fn bind_version(&mut self) {
+        let version_str = self.version_str();
+        self.meta_ad(version_str, /* more */ false);
+    }
+
+    /// Returns a short tag identifying this `Strobe`'s configuration, i.e., its version string and
+    /// rate. Peers can exchange and compare their tags to detect, e.g., one side using
+    /// `SecParam::B128` and the other `SecParam::B256`, before exchanging any ciphertext. The tag
+    /// depends only on the configuration, not on the state, so it is safe to send in the clear.
+    ///
+    /// This is not a substitute for a real handshake. It only catches the most common
+    /// misconfiguration, and an active attacker can trivially forge it.
+    pub fn security_tag(&self) -> [u8; 16] {
+        let mut s = Strobe::new(b"strobe-rs security tag", SecParam::B128);
+        s.meta_ad(self.version_str(), false);
+        s.meta_ad((self.rate as u64).to_le_bytes(), false);
+
+        let mut tag = [0u8; 16];
+        s.prf(&mut tag, false);
+        tag
+    }
+
     /// Validates that the `more` flag is being used correctly. Panics when validation fails.
     fn validate_streaming(&mut self, flags: OpFlags, more: bool) {
         // Streaming only makes sense if this operation is the same as last. For example you can do
@@ -230,90 +1104,109 @@ impl Strobe {
         keccakf_u8(&mut self.st);
         self.pos = 0;
         self.pos_begin = 0;
+        self.perm_count = self.perm_count.saturating_add(1);
+    }
+
+    /// Returns how many of the next `len` bytes fit in the state before the rate boundary
+    fn chunk_len(&self, len: usize) -> usize {
+        core::cmp::min(self.rate - self.pos, len)
+    }
+
+    /// Moves `pos` forward by `n` bytes, running the permutation if we hit the rate boundary
+    fn advance(&mut self, n: usize) {
+        self.pos += n;
+        if self.pos == self.rate {
+            self.run_f();
+        }
     }
 
     /// XORs the given data into the state. This is a special case of the `duplex` code in the
     /// STROBE paper.
-    fn absorb(&mut self, data: &[u8]) {
-        for b in data {
-            self.st.0[self.pos] ^= *b;
-
-            self.pos += 1;
-            if self.pos == self.rate {
-                self.run_f();
-            }
+    fn absorb(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let n = self.chunk_len(data.len());
+            let (chunk, rest) = data.split_at(n);
+            let st_chunk = &mut self.st.0[self.pos..self.pos + n];
+            lanewise(self.pos, st_chunk, chunk, |s, d| *s ^= d);
+
+            self.advance(n);
+            data = rest;
         }
     }
 
     /// XORs the given data into the state, then sets the data equal the state.  This is a special
     /// case of the `duplex` code in the STROBE paper.
-    fn absorb_and_set(&mut self, data: &mut [u8]) {
-        for b in data {
-            let state_byte = self.st.0.get_mut(self.pos).unwrap();
-            *state_byte ^= *b;
-            *b = *state_byte;
-
-            self.pos += 1;
-            if self.pos == self.rate {
-                self.run_f();
-            }
+    fn absorb_and_set(&mut self, mut data: &mut [u8]) {
+        while !data.is_empty() {
+            let n = self.chunk_len(data.len());
+            let (chunk, rest) = data.split_at_mut(n);
+            let st_chunk = &mut self.st.0[self.pos..self.pos + n];
+            lanewise_mut(self.pos, st_chunk, chunk, |s, d| {
+                *s ^= *d;
+                *d = *s;
+            });
+
+            self.advance(n);
+            data = rest;
         }
     }
 
     /// Copies the internal state into the given buffer. This is a special case of `absorb_and_set`
     /// where `data` is all zeros.
-    fn copy_state(&mut self, data: &mut [u8]) {
-        for b in data {
-            *b = self.st.0[self.pos];
-
-            self.pos += 1;
-            if self.pos == self.rate {
-                self.run_f();
-            }
+    fn copy_state(&mut self, mut data: &mut [u8]) {
+        while !data.is_empty() {
+            let n = self.chunk_len(data.len());
+            let (chunk, rest) = data.split_at_mut(n);
+            chunk.copy_from_slice(&self.st.0[self.pos..self.pos + n]);
+
+            self.advance(n);
+            data = rest;
         }
     }
 
     /// Overwrites the state with the given data while XORing the given data with the old state.
     /// This is a special case of the `duplex` code in the STROBE paper.
-    fn exchange(&mut self, data: &mut [u8]) {
-        for b in data {
-            let state_byte = self.st.0.get_mut(self.pos).unwrap();
-            *b ^= *state_byte;
-            *state_byte ^= *b;
-
-            self.pos += 1;
-            if self.pos == self.rate {
-                self.run_f();
-            }
+    fn exchange(&mut self, mut data: &mut [u8]) {
+        while !data.is_empty() {
+            let n = self.chunk_len(data.len());
+            let (chunk, rest) = data.split_at_mut(n);
+            let st_chunk = &mut self.st.0[self.pos..self.pos + n];
+            lanewise_mut(self.pos, st_chunk, chunk, |s, d| {
+                *d ^= *s;
+                *s ^= *d;
+            });
+
+            self.advance(n);
+            data = rest;
         }
     }
 
     /// Overwrites the state with the given data. This is a special case of `Strobe::exchange`,
     /// where we do not want to mutate the input data.
-    fn overwrite(&mut self, data: &[u8]) {
-        for b in data {
-            self.st.0[self.pos] = *b;
-
-            self.pos += 1;
-            if self.pos == self.rate {
-                self.run_f();
-            }
+    fn overwrite(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let n = self.chunk_len(data.len());
+            let (chunk, rest) = data.split_at(n);
+            self.st.0[self.pos..self.pos + n].copy_from_slice(chunk);
+
+            self.advance(n);
+            data = rest;
         }
     }
 
     /// Copies the state into the given buffer and sets the state to 0. This is a special case of
     /// `Strobe::exchange`, where `data` is assumed to be the all-zeros string. This is precisely
     /// the case when the current operation is PRF.
-    fn squeeze(&mut self, data: &mut [u8]) {
-        for b in data {
-            let state_byte = self.st.0.get_mut(self.pos).unwrap();
-            *b = *state_byte;
-            *state_byte = 0;
-
-            self.pos += 1;
-            if self.pos == self.rate {
-                self.run_f();
-            }
+    fn squeeze(&mut self, mut data: &mut [u8]) {
+        while !data.is_empty() {
+            let n = self.chunk_len(data.len());
+            let (chunk, rest) = data.split_at_mut(n);
+            let st_chunk = &mut self.st.0[self.pos..self.pos + n];
+            chunk.copy_from_slice(st_chunk);
+            st_chunk.fill(0);
+
+            self.advance(n);
+            data = rest;
         }
     }
 
@@ -322,19 +1215,13 @@ impl Strobe {
     /// `Strobe::squeeze`. It's like `squeeze` in that we assume we've been given all zeros as
     /// input, and like `overwrite` in that we do not mutate (or take) any input.
     fn zero_state(&mut self, mut bytes_to_zero: usize) {
-        static ZEROS: [u8; 8 * KECCAK_BLOCK_SIZE] = [0u8; 8 * KECCAK_BLOCK_SIZE];
-
         // Do the zero-writing in chunks
         while bytes_to_zero > 0 {
-            let slice_len = core::cmp::min(self.rate - self.pos, bytes_to_zero);
-            self.st.0[self.pos..(self.pos + slice_len)].copy_from_slice(&ZEROS[..slice_len]);
-
-            self.pos += slice_len;
-            bytes_to_zero -= slice_len;
+            let n = self.chunk_len(bytes_to_zero);
+            self.st.0[self.pos..self.pos + n].fill(0);
 
-            if self.pos == self.rate {
-                self.run_f();
-            }
+            bytes_to_zero -= n;
+            self.advance(n);
         }
     }
 
@@ -353,6 +1240,10 @@ impl Strobe {
             flags.set(OpFlags::I, self.is_receiver.unwrap() != is_op_receiving);
         }
 
+        if flags & !OpFlags::M == KEY_FLAGS {
+            self.keyed = true;
+        }
+
         let old_pos_begin = self.pos_begin;
         self.pos_begin = self.pos + 1;
 
@@ -360,16 +1251,57 @@ impl Strobe {
         let to_mix = &mut [old_pos_begin as u8, flags.bits()];
         self.absorb(&to_mix[..]);
 
+        // Ops that use cipher output must see a state that depends on everything absorbed so far,
+        // including the two bytes above, so they have to start on a fresh permutation. This
+        // condition is exactly the spec's. Starting from position p, the absorb above leaves:
+        //   * pos == p + 2 >= 2 if p + 2 < rate. Nothing has been permuted, so F is needed.
+        //   * pos == 0 if p + 2 == rate. The second byte filled the block and ran F, and nothing
+        //     has been absorbed since, so no further F is needed.
+        //   * pos == 1 if p + 1 == rate. The first byte filled the block and ran F, but the
+        //     second byte was absorbed after it, so F is needed again.
+        // So F is needed exactly when pos != 0. Consecutive C ops each need their own F, since
+        // the previous op's output and this op's begin bytes were mixed in after the last one.
         let force_f = flags.contains(OpFlags::C) || flags.contains(OpFlags::K);
         if force_f && self.pos != 0 {
             self.run_f();
         }
     }
 
+    /// Returns the number of bytes of output that the typed operation with the given flags writes
+    /// into its buffer, for an input of `input_len` bytes. The `M` flag is ignored.
+    ///
+    /// `send_enc`, `recv_enc`, `prf`, and `send_mac` overwrite their whole buffer, so their output
+    /// is `input_len` bytes. Every other typed operation, including `key`, `recv_mac`, and
+    /// `ratchet`, produces no output. For flags that don't belong to a typed operation, this
+    /// follows [`Strobe::operate`], which writes its whole buffer iff the flags contain `C`.
+    pub fn output_len(flags: OpFlags, input_len: usize) -> usize {
+        let flags = flags & !OpFlags::M;
+        if flags == KEY_FLAGS || flags == RECV_MAC_FLAGS || flags == RATCHET_FLAGS {
+            0
+        } else if flags.contains(OpFlags::C) {
+            input_len
+        } else {
+            0
+        }
+    }
+
     /// Performs the state / data transformation that corresponds to the given flags. If `more` is
     /// given, this will treat `data` as a continuation of the data given in the previous
-    /// call to `operate`.
-    pub(crate) fn operate(&mut self, flags: OpFlags, data: &mut [u8], more: bool) {
+    /// call to `operate`. This is the raw STROBE `duplex` operation, and it is what every typed
+    /// method (`send_enc`, `ad`, etc.) is built on.
+    ///
+    /// **WARNING:** This is a low-level foot-gun, meant for nonstandard constructions that the
+    /// typed methods do not cover. Prefer the typed methods wherever possible. Using flag
+    /// combinations that don't appear in the STROBE spec, or using different flags than your peer
+    /// does, will silently desynchronize the sender and receiver, and can undermine the security
+    /// of your protocol.
+    ///
+    /// As with the typed methods, the output of the `send_mac` flags (`C | T`) and `prf` flags
+    /// (`I | A | C`) is independent of the initial contents of `data`. If the flags do not contain
+    /// `C`, then `data` is left unmodified.
+    ///
+    /// Panics if `flags` contains `OpFlags::K`, which is not implemented, or if `more` is misused.
+    pub fn operate(&mut self, flags: OpFlags, data: &mut [u8], more: bool) {
         // Make sure the K opflag isn't being used, and that the `more` flag isn't being misused
         assert!(!flags.contains(OpFlags::K), "Op flag K not implemented");
         self.validate_streaming(flags, more);
@@ -378,6 +1310,9 @@ impl Strobe {
         if !more {
             self.begin_op(flags);
         }
+        self.count_bytes(data.len());
+        #[cfg(feature = "trace")]
+        trace_op(flags, more, data.len());
 
         // Meta-ness is only relevant for `begin_op`. Remove it to simplify the below logic.
         let flags = flags & !OpFlags::M;
@@ -386,13 +1321,13 @@ impl Strobe {
         if flags.contains(OpFlags::C) && flags.contains(OpFlags::T) && !flags.contains(OpFlags::I) {
             // This is equivalent to the `duplex` operation in the Python implementation, with
             // `cafter = True`
-            if flags == OpFlags::C | OpFlags::T {
+            if flags == SEND_MAC_FLAGS {
                 // This is `send_mac`. Pretend the input is all zeros
                 self.copy_state(data)
             } else {
                 self.absorb_and_set(data);
             }
-        } else if flags == OpFlags::I | OpFlags::A | OpFlags::C {
+        } else if flags == PRF_FLAGS {
             // Special case of case below. This is PRF. Use `squeeze` instead of `exchange`.
             self.squeeze(data);
         } else if flags.contains(OpFlags::C) {
@@ -400,16 +1335,35 @@ impl Strobe {
             // `cbefore = True`
             self.exchange(data);
         } else {
-            // This should normally call `absorb`, but `absorb` does not mutate, so the implementor
-            // should have used operate_no_mutate instead
-            panic!("operate should not be called for operations that do not require mutation");
+            // This is equivalent to the `duplex` operation in the Python implementation, with
+            // `cbefore = cafter = False`. The typed methods use operate_no_mutate for this case,
+            // but raw callers may end up here.
+            self.absorb(data);
         }
     }
 
-    /// Performs the state transformation that corresponds to the given flags. If `more` is given,
-    /// this will treat `data` as a continuation of the data given in the previous call to
-    /// `operate`. This uses non-mutating variants of the specializations of the `duplex` function.
-    pub(crate) fn operate_no_mutate(&mut self, flags: OpFlags, data: &[u8], more: bool) {
+    /// Performs the state transformation that corresponds to the given flags, without mutating
+    /// `data`. If `more` is given, this will treat `data` as a continuation of the data given in
+    /// the previous call to `operate`. This uses non-mutating variants of the specializations of
+    /// the `duplex` function.
+    ///
+    /// If `flags` does not contain `C`, `data` is *absorbed*, i.e., XORed into the state, as in
+    /// `ad` and `send_clr`. Every byte already in the state keeps influencing the transcript. This
+    /// is correct for public data.
+    ///
+    /// If `flags` contains `C`, `data` *overwrites* the state, as in `key`. The state bytes it
+    /// replaces are forgotten, which gives forward secrecy, but means the result depends only on
+    /// `data` at those positions. This is what the receiver of an encrypted message does with the
+    /// ciphertext, so `operate_no_mutate(RECV_ENC_FLAGS, ct, more)` leaves the state exactly as
+    /// `recv_enc` would, without decrypting `ct`. Use this for keying material, or for bytes that
+    /// are deliberately meant to replace, rather than extend, what came before.
+    ///
+    /// **WARNING:** The same caveats as for [`Strobe::operate`] apply. Both peers must use the
+    /// same flags.
+    ///
+    /// Panics if `flags` contains `OpFlags::K`, if `more` is misused, or if `flags` contains `C`
+    /// and `T` but not `I` (i.e., `send_enc` or `send_mac`), since those always produce output.
+    pub fn operate_no_mutate(&mut self, flags: OpFlags, data: &[u8], more: bool) {
         // Make sure the K opflag isn't being used, and that the `more` flag isn't being misused
         assert!(!flags.contains(OpFlags::K), "Op flag K not implemented");
         self.validate_streaming(flags, more);
@@ -418,6 +1372,9 @@ impl Strobe {
         if !more {
             self.begin_op(flags);
         }
+        self.count_bytes(data.len());
+        #[cfg(feature = "trace")]
+        trace_op(flags, more, data.len());
 
         // There are no non-mutating variants of things with flags & (C | T | I) == C | T
         if flags.contains(OpFlags::C) && flags.contains(OpFlags::T) && !flags.contains(OpFlags::I) {
@@ -440,55 +1397,164 @@ impl Strobe {
         mac: &[u8; N],
         is_meta: bool,
     ) -> Result<(), AuthError> {
-        // Make a temp buffer for the MAC. This is because operate() mutates the buffer
+        // If the buffer isn't all zeros, that's an invalid MAC. A zero-length MAC is trivially all
+        // zeros, but never authenticates anything, so it's invalid too.
+        let all_zero = self.generalized_recv_mac_with(mac, is_meta, is_all_zero);
+        if all_zero && N != 0 {
+            Ok(())
+        } else {
+            Err(AuthError)
+        }
+    }
+
+    // Runs a (meta_)recv_mac and applies `check` to the result, which is all zeros iff the MAC is
+    // valid
+    fn generalized_recv_mac_with<const N: usize, T>(
+        &mut self,
+        mac: &[u8; N],
+        is_meta: bool,
+        check: fn(&[u8]) -> T,
+    ) -> T {
+        // Make a temp buffer for the MAC. This is because operate() mutates the buffer
         let mut mac_copy = *mac;
 
         // These are the (meta_)recv_mac flags
         let flags = if is_meta {
-            OpFlags::I | OpFlags::C | OpFlags::T | OpFlags::M
+            RECV_MAC_FLAGS | OpFlags::M
         } else {
-            OpFlags::I | OpFlags::C | OpFlags::T
+            RECV_MAC_FLAGS
         };
         // recv_mac can never be streamed
         self.operate(flags, &mut mac_copy, /* more */ false);
 
-        // Constant-time MAC check. This accumulates the truth values of byte == 0
-        let mut all_zero = subtle::Choice::from(1u8);
-        for b in mac_copy {
-            all_zero &= b.ct_eq(&0u8);
-        }
+        // Constant-time MAC check
+        let res = check(&mac_copy);
 
         // Zeroize the temp buffer
         mac_copy.zeroize();
 
-        // If the buffer isn't all zeros, that's an invalid MAC
-        if !bool::from(all_zero) {
-            Err(AuthError)
-        } else {
-            Ok(())
-        }
+        res
     }
 
     /// Attempts to authenticate the current state against the given MAC. On failure, it returns an
-    /// `AuthError`.
+    /// `AuthError`. A zero-length MAC always fails.
+    ///
+    /// Unlike the other operations, this takes no `more` flag: a MAC is always verified in a
+    /// single call, so the verdict covers every byte of it. A MAC that arrives in pieces must be
+    /// reassembled before calling this. Splitting it across several calls would verify each piece
+    /// as a separate, shorter MAC of a different state, and so would fail.
     pub fn recv_mac<const N: usize>(&mut self, mac: &[u8; N]) -> Result<(), AuthError> {
         self.generalized_recv_mac(mac, /* is_meta */ false)
     }
 
     /// Attempts to authenticate the current state against the given MAC. On failure, it returns an
-    /// `AuthError`.
+    /// `AuthError`. A zero-length MAC always fails.
     pub fn meta_recv_mac<const N: usize>(&mut self, mac: &[u8; N]) -> Result<(), AuthError> {
         self.generalized_recv_mac(mac, /* is_meta */ true)
     }
 
+    /// Attempts to authenticate the current state against the MAC `tag`, which is expected to be
+    /// `expected_len` bytes long. On failure, it returns an `AuthError`. This is for protocols
+    /// where the tag length is a runtime parameter, e.g., negotiated between 16 and 32 bytes.
+    ///
+    /// The state always advances exactly as it would for a `recv_mac` on an `expected_len`-byte
+    /// MAC, whose bytes are those of `tag`, truncated or padded with zeros to `expected_len`.
+    /// A wrong-length `tag` is then rejected along with every other invalid tag, so a wrong length
+    /// gives the same result and the same resulting state as a wrong tag of the right length. As
+    /// with `recv_mac`, an `expected_len` of 0 always fails.
+    ///
+    /// Only the final comparison is constant time. Copying `tag` in branches on its length, so
+    /// the timing of this call can reveal whether `tag.len()` equals `expected_len`.
+    pub fn recv_mac_len(&mut self, tag: &[u8], expected_len: usize) -> Result<(), AuthError> {
+        let mut buf = [0u8; 64];
+        // ORs together every byte of the processed MAC, so that a valid MAC leaves this at 0
+        let mut acc = 0u8;
+
+        let mut offset = 0;
+        let mut more = false;
+        while offset < expected_len || !more {
+            let n = core::cmp::min(buf.len(), expected_len - offset);
+            for (i, b) in buf[..n].iter_mut().enumerate() {
+                *b = tag.get(offset + i).copied().unwrap_or(0);
+            }
+
+            // A MAC is processed as a single recv_mac operation, streamed one chunk at a time
+            self.operate(RECV_MAC_FLAGS, &mut buf[..n], more);
+            acc = buf[..n].iter().fold(acc, |acc, b| acc | b);
+
+            offset += n;
+            more = true;
+        }
+        buf.zeroize();
+
+        let bad_len = (tag.len() != expected_len) as u8 | (expected_len == 0) as u8;
+        if is_all_zero(&[acc, bad_len]) {
+            Ok(())
+        } else {
+            Err(AuthError)
+        }
+    }
+
+    /// Attempts to authenticate the current state against each of the given MACs in turn, and
+    /// returns a single aggregate result. On failure, it returns an `AuthError`.
+    ///
+    /// The state advances exactly as it would for a `recv_mac` call on each MAC, in order. Every
+    /// MAC is processed even if an earlier one fails, and the results are combined in constant
+    /// time, so a failure does not reveal which MAC was invalid. An empty `macs` succeeds
+    /// vacuously, but zero-length MACs always fail.
+    pub fn recv_macs<const N: usize>(&mut self, macs: &[[u8; N]]) -> Result<(), AuthError> {
+        #[cfg(feature = "subtle")]
+        let all_valid = {
+            let mut all_valid = subtle::Choice::from((N != 0 || macs.is_empty()) as u8);
+            for mac in macs {
+                all_valid &= self.generalized_recv_mac_with(mac, false, ct_is_all_zero);
+            }
+            bool::from(all_valid)
+        };
+        #[cfg(not(feature = "subtle"))]
+        let all_valid = {
+            let mut all_valid = N != 0 || macs.is_empty();
+            for mac in macs {
+                all_valid &= self.generalized_recv_mac_with(mac, false, is_all_zero);
+            }
+            all_valid
+        };
+
+        if all_valid {
+            Ok(())
+        } else {
+            Err(AuthError)
+        }
+    }
+
+    /// Attempts to authenticate the current state against the given MAC, and returns whether it
+    /// succeeded as a `Choice`. This is the same as `recv_mac`, including failing on a zero-length
+    /// MAC, but lets the result be combined
+    /// with other constant-time checks before branching on it.
+    #[cfg(feature = "subtle")]
+    pub fn recv_mac_ct<const N: usize>(&mut self, mac: &[u8; N]) -> subtle::Choice {
+        self.generalized_recv_mac_with(mac, /* is_meta */ false, ct_is_all_zero)
+            & subtle::Choice::from((N != 0) as u8)
+    }
+
+    /// Attempts to authenticate the current state against the given MAC, and returns whether it
+    /// succeeded as a `Choice`. This is the same as `meta_recv_mac`, including failing on a
+    /// zero-length MAC, but lets the result be combined with other constant-time checks before
+    /// branching on it.
+    #[cfg(feature = "subtle")]
+    pub fn meta_recv_mac_ct<const N: usize>(&mut self, mac: &[u8; N]) -> subtle::Choice {
+        self.generalized_recv_mac_with(mac, /* is_meta */ true, ct_is_all_zero)
+            & subtle::Choice::from((N != 0) as u8)
+    }
+
     // This is separately defined because it's the only method that takes an integer and mutates
     // its input
     fn generalized_ratchet(&mut self, num_bytes_to_zero: usize, more: bool, is_meta: bool) {
         // These are the (meta_)ratchet flags
         let flags = if is_meta {
-            OpFlags::C | OpFlags::M
+            RATCHET_FLAGS | OpFlags::M
         } else {
-            OpFlags::C
+            RATCHET_FLAGS
         };
 
         // We don't make an `operate` call, since this is a super special case. That means we have
@@ -497,6 +1563,9 @@ impl Strobe {
         if !more {
             self.begin_op(flags);
         }
+        self.count_bytes(num_bytes_to_zero);
+        #[cfg(feature = "trace")]
+        trace_op(flags, more, num_bytes_to_zero);
 
         self.zero_state(num_bytes_to_zero);
     }
@@ -517,6 +1586,904 @@ impl Strobe {
         self.generalized_ratchet(num_bytes_to_zero, more, /* is_meta */ true)
     }
 
+    /// Ratchets the internal state forward by zeroing a full block, i.e., `rate` bytes. This is
+    /// the same as `ratchet(rate, false)`.
+    ///
+    /// A full block is the natural forward-secrecy boundary: ratcheting begins by running
+    /// Keccak-f, then zeroes the entire public part of the state, so nothing that came before can
+    /// be recovered from the state afterwards. Smaller ratchets leave some of the pre-ratchet
+    /// public state intact, and the security of the ratchet then rests on the capacity alone.
+    pub fn ratchet_full(&mut self) {
+        self.ratchet(self.rate, /* more */ false)
+    }
+
+    /// Sets a symmetric cipher key, then zeroizes `key`. This is equivalent to `key(key, false)`
+    /// followed by wiping the caller's buffer, so that the key material doesn't linger in memory.
+    pub fn key_zeroizing(&mut self, key: &mut [u8]) {
+        self.key(&*key, /* more */ false);
+        key.zeroize();
+    }
+
+    /// Sends an encrypted message and returns the ciphertext, leaving `plaintext` untouched. This
+    /// copies `plaintext` and runs `send_enc` on the copy, so the output and resulting state are
+    /// identical to those of `send_enc`.
+    #[cfg(feature = "alloc")]
+    pub fn send_enc_to_vec(&mut self, plaintext: &[u8], more: bool) -> Vec<u8> {
+        let mut buf = plaintext.to_vec();
+        self.send_enc(&mut buf, more);
+        buf
+    }
+
+    /// Returns an iterator that encrypts each of `chunks` with `send_enc` as it is pulled. Once the
+    /// iterator is exhausted, the state is identical to that of a single `send_enc(data, false)`
+    /// on the concatenation of all the chunks. See [`EncIter`].
+    #[cfg(feature = "alloc")]
+    pub fn send_enc_iter<I: IntoIterator<Item = Vec<u8>>>(
+        &mut self,
+        chunks: I,
+    ) -> EncIter<'_, I::IntoIter> {
+        EncIter::new(self, chunks.into_iter(), Strobe::send_enc)
+    }
+
+    /// Returns an iterator that decrypts each of `chunks` with `recv_enc` as it is pulled. This is
+    /// the counterpart to [`Strobe::send_enc_iter`]. Nothing here is authenticated, so the output
+    /// must not be trusted until a subsequent `recv_mac` succeeds.
+    #[cfg(feature = "alloc")]
+    pub fn recv_enc_iter<I: IntoIterator<Item = Vec<u8>>>(
+        &mut self,
+        chunks: I,
+    ) -> EncIter<'_, I::IntoIter> {
+        EncIter::new(self, chunks.into_iter(), Strobe::recv_enc)
+    }
+
+    /// Reads `r` to its end, mixing everything read into the state as associated data. Returns
+    /// the number of bytes read.
+    ///
+    /// The data is read in chunks into a stack buffer, so the stream is never held in memory in
+    /// full. The resulting state is identical to that of a single `ad(data, false)` on the
+    /// concatenation of everything read.
+    ///
+    /// If `r` returns an error, the state has absorbed everything read up to that point, and the
+    /// `ad` operation can be continued with `more = true`.
+    #[cfg(feature = "std")]
+    pub fn ad_from_reader(&mut self, r: &mut impl std::io::Read) -> std::io::Result<u64> {
+        let mut buf = [0u8; IO_CHUNK_LEN];
+        let mut total = 0u64;
+        let mut more = false;
+
+        loop {
+            let n = match r.read(&mut buf) {
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            // Even an empty stream has to begin the operation
+            if n == 0 && more {
+                break;
+            }
+
+            self.ad(&buf[..n], more);
+            total += n as u64;
+            more = true;
+
+            if n == 0 {
+                break;
+            }
+        }
+
+        Ok(total)
+    }
+
+    /// Reads `src` to its end, encrypts everything read with `send_enc`, and writes the ciphertext
+    /// to `dst`. Returns the number of bytes encrypted.
+    ///
+    /// The data is processed in chunks in a stack buffer, so the stream is never held in memory in
+    /// full. The resulting state is identical to that of a single `send_enc(data, false)` on the
+    /// concatenation of everything read.
+    #[cfg(feature = "std")]
+    pub fn send_enc_stream(
+        &mut self,
+        src: &mut impl std::io::Read,
+        dst: &mut impl std::io::Write,
+    ) -> std::io::Result<u64> {
+        self.enc_stream(src, dst, Strobe::send_enc)
+    }
+
+    /// Reads `src` to its end, decrypts everything read with `recv_enc`, and writes the plaintext
+    /// to `dst`. Returns the number of bytes decrypted. This is the counterpart to
+    /// [`Strobe::send_enc_stream`].
+    ///
+    /// The resulting state is identical to that of a single `recv_enc(data, false)` on the
+    /// concatenation of everything read. Nothing here is authenticated, so the plaintext written
+    /// to `dst` must not be trusted until a subsequent `recv_mac` succeeds.
+    #[cfg(feature = "std")]
+    pub fn recv_enc_stream(
+        &mut self,
+        src: &mut impl std::io::Read,
+        dst: &mut impl std::io::Write,
+    ) -> std::io::Result<u64> {
+        self.enc_stream(src, dst, Strobe::recv_enc)
+    }
+
+    /// Returns a writer that encrypts everything written to it with `send_enc`, and forwards the
+    /// ciphertext to `inner`. Call [`EncWriter::finish`] when done. See [`EncWriter`].
+    #[cfg(feature = "std")]
+    pub fn enc_writer<W: std::io::Write>(&mut self, inner: W) -> EncWriter<'_, W> {
+        EncWriter::new(self, inner)
+    }
+
+    /// Returns a reader that reads ciphertext from `inner`, and decrypts it with `recv_enc`. Call
+    /// [`DecReader::finish`] when done. See [`DecReader`].
+    #[cfg(feature = "std")]
+    pub fn dec_reader<R: std::io::Read>(&mut self, inner: R) -> DecReader<'_, R> {
+        DecReader::new(self, inner)
+    }
+
+    // Runs a streaming send_enc or recv_enc from src to dst. Like ad_from_reader, the op is begun
+    // even if src is empty.
+    #[cfg(feature = "std")]
+    fn enc_stream(
+        &mut self,
+        src: &mut impl std::io::Read,
+        dst: &mut impl std::io::Write,
+        op: fn(&mut Strobe, &mut [u8], bool),
+    ) -> std::io::Result<u64> {
+        let mut buf = [0u8; IO_CHUNK_LEN];
+        let mut total = 0u64;
+        let mut more = false;
+
+        let res = loop {
+            let n = match src.read(&mut buf) {
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => break Err(e),
+            };
+            if n == 0 && more {
+                break Ok(total);
+            }
+
+            op(self, &mut buf[..n], more);
+            total += n as u64;
+            more = true;
+
+            if let Err(e) = dst.write_all(&buf[..n]) {
+                break Err(e);
+            }
+            if n == 0 {
+                break Ok(total);
+            }
+        };
+
+        // The buffer held plaintext
+        buf.zeroize();
+        res
+    }
+
+    /// Derives key material into `out`, using `info` as a context string. This runs
+    /// `meta_ad(info, false)` then `prf(out, false)`, which makes `Strobe` an HKDF-like KDF: key
+    /// the state with the input keying material, then call this once per derived key.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// # use strobe_rs::{SecParam, Strobe};
+    /// let mut s = Strobe::new(b"derive_key example", SecParam::B256);
+    /// s.key(b"input keying material", false);
+    ///
+    /// let mut enc_key = [0u8; 32];
+    /// let mut mac_key = [0u8; 32];
+    /// s.clone().derive_key(b"encryption", &mut enc_key);
+    /// s.clone().derive_key(b"authentication", &mut mac_key);
+    /// assert_ne!(enc_key, mac_key);
+    /// ```
+    pub fn derive_key(&mut self, info: &[u8], out: &mut [u8]) {
+        self.meta_ad(info, false);
+        self.prf(out, false);
+    }
+
+    /// Derives an `N`-byte key, using `info` as a context string. This is the same as
+    /// [`Strobe::derive_key`], but returns its output by value.
+    pub fn derive_key_array<const N: usize>(&mut self, info: &[u8]) -> [u8; N] {
+        let mut out = [0u8; N];
+        self.derive_key(info, &mut out);
+        out
+    }
+
+    /// Writes pseudorandom bytes derived from the internal state into `out`, domain-separated by
+    /// `label`. This is [`Strobe::separator`] on `label`, followed by `prf(out, false)`.
+    ///
+    /// Because the label is length-framed, two labels where one is a prefix of the other, e.g.,
+    /// `b"a"` and `b"ab"`, can never produce the same transcript.
+    pub fn labeled_prf(&mut self, label: &[u8], out: &mut [u8]) {
+        self.separator(label);
+        self.prf(out, /* more */ false);
+    }
+
+    /// Returns `N` pseudorandom bytes derived from the internal state, domain-separated by
+    /// `label`. This is the same as [`Strobe::labeled_prf`], but returns its output by value.
+    pub fn labeled_prf_array<const N: usize>(&mut self, label: &[u8]) -> [u8; N] {
+        let mut out = [0u8; N];
+        self.labeled_prf(label, &mut out);
+        out
+    }
+
+    /// Splits this `Strobe` into independent sending and receiving halves, returned as
+    /// `(send, recv)`. This is meant for full-duplex transports, à la the Noise `Split()`
+    /// function.
+    ///
+    /// The initiator (the peer whose first transport operation was a send) derives its sending half
+    /// with `meta_ad(b"->", false)` and its receiving half with `meta_ad(b"<-", false)`. The
+    /// responder does the opposite, so that each peer's sending half matches the other peer's
+    /// receiving half.
+    ///
+    /// Panics if no transport operation (`send_*`/`recv_*`) has been performed yet, since the
+    /// peer's role is undetermined.
+    pub fn split(self) -> (Strobe, Strobe) {
+        let is_receiver = self
+            .is_receiver
+            .expect("cannot split before the first transport operation");
+        let (send_label, recv_label): (&[u8], &[u8]) = if is_receiver {
+            (b"<-", b"->")
+        } else {
+            (b"->", b"<-")
+        };
+
+        let mut send = self.clone();
+        let mut recv = self;
+        send.meta_ad(send_label, false);
+        recv.meta_ad(recv_label, false);
+
+        (send, recv)
+    }
+
+    /// Mixes the concatenation of `chunks` into the internal state as associated data. This is
+    /// a single `ad` operation, i.e., the resulting state is identical to that of
+    /// `ad(&chunks.concat(), false)`, but the chunks are never copied into one buffer.
+    pub fn ad_many<'a>(&mut self, chunks: impl IntoIterator<Item = &'a [u8]>) {
+        let mut more = false;
+        for chunk in chunks {
+            self.ad(chunk, more);
+            more = true;
+        }
+
+        // An empty iterator is the same as an empty ad
+        if !more {
+            self.ad([], false);
+        }
+    }
+
+    /// Mixes the bytes yielded by `it` into the internal state as associated data. This is a
+    /// single `ad` operation, or a continuation of one if `more` is set, i.e., the resulting state
+    /// is identical to that of `ad` on the collected bytes. This doesn't allocate; the bytes are
+    /// absorbed in small batches on the stack.
+    pub fn ad_iter(&mut self, it: impl IntoIterator<Item = u8>, more: bool) {
+        let mut it = it.into_iter();
+        let mut more = more;
+        let mut buf = [0u8; 64];
+        loop {
+            let mut n = 0;
+            for (slot, b) in buf.iter_mut().zip(&mut it) {
+                *slot = b;
+                n += 1;
+            }
+
+            // An empty batch only matters if it's the whole operation
+            if n > 0 || !more {
+                self.ad(&buf[..n], more);
+                more = true;
+            }
+            if n < buf.len() {
+                break;
+            }
+        }
+    }
+
+    /// Mixes the concatenation of `slices` into the internal state as associated data, e.g., the
+    /// buffers of a vectored read. This is a single `ad` operation, or a continuation of one if
+    /// `more` is set, i.e., the resulting state is identical to that of `ad` on the
+    /// concatenation, but the slices are never copied into one buffer.
+    #[cfg(feature = "std")]
+    pub fn ad_ioslices(&mut self, slices: &[std::io::IoSlice<'_>], more: bool) {
+        let mut more = more;
+        for slice in slices {
+            self.ad(&**slice, more);
+            more = true;
+        }
+
+        // No slices is the same as an empty ad
+        if !more {
+            self.ad([], false);
+        }
+    }
+
+    /// Keys the state with the concatenation of `shares`, e.g., the shares of a key in a threshold
+    /// setting. This is a single `key` operation, i.e., the resulting state is identical to that
+    /// of `key(&shares.concat(), false)`, but the shares are never copied into one buffer.
+    pub fn key_shares(&mut self, shares: &[&[u8]]) {
+        let mut more = false;
+        for share in shares {
+            self.key(share, more);
+            more = true;
+        }
+
+        // No shares is the same as an empty key
+        if !more {
+            self.key([], false);
+        }
+    }
+
+    /// Mixes associated data into the internal state, prefixed by its length. This runs
+    /// `meta_ad` on the length of `data`, encoded as an 8-byte little-endian integer, then runs
+    /// `ad(data, false)`.
+    ///
+    /// Unlike a sequence of plain `ad` calls, a sequence of `ad_framed` calls is unambiguous:
+    /// bytes cannot be shifted between adjacent fields without changing the transcript.
+    pub fn ad_framed(&mut self, data: &[u8]) {
+        self.meta_ad(framed_len(data), false);
+        self.ad(data, false);
+    }
+
+    /// Marks a domain separation point in the transcript, named by `label`. This is a
+    /// length-framed `meta_ad`, with the same framing as [`Strobe::ad_framed`]: it runs `meta_ad`
+    /// on the length of `label`, encoded as an 8-byte little-endian integer, then runs
+    /// `meta_ad(label, false)`.
+    ///
+    /// Because the label is length-framed, a separator can never be confused with a different
+    /// separator, nor with the data around it.
+    pub fn separator(&mut self, label: &[u8]) {
+        self.meta_ad(framed_len(label), false);
+        self.meta_ad(label, /* more */ false);
+    }
+
+    /// Begins a length-framed associated data operation whose data is `len` bytes long, and is
+    /// given by subsequent calls to `ad`. This is for data that is too large to be in memory at
+    /// once, or whose length doesn't fit in a `usize`, e.g., a 5GiB stream on a 32-bit target.
+    ///
+    /// This runs `meta_ad` on `len`, encoded as an 8-byte little-endian integer. Following this
+    /// with `ad(data, false)`, or an equivalent sequence of streaming `ad` calls, gives the same
+    /// state as `ad_framed(data)`. It is up to the caller to make sure that exactly `len` bytes
+    /// follow.
+    pub fn begin_ad_framed(&mut self, len: u64) {
+        self.meta_ad(encode_framed_len(len), false);
+    }
+
+    /// Sends a plaintext message, prefixed by its length. This runs `meta_send_clr` on the length
+    /// of `data`, encoded as an 8-byte little-endian integer, then runs `send_clr(data, false)`.
+    /// The receiver should call `recv_clr_framed`.
+    pub fn send_clr_framed(&mut self, data: &[u8]) {
+        self.meta_send_clr(framed_len(data), false);
+        self.send_clr(data, false);
+    }
+
+    /// Receives a plaintext message, prefixed by its length. This runs `meta_recv_clr` on the
+    /// length of `data`, encoded as an 8-byte little-endian integer, then runs
+    /// `recv_clr(data, false)`. This is the counterpart to `send_clr_framed`.
+    pub fn recv_clr_framed(&mut self, data: &[u8]) {
+        self.meta_recv_clr(framed_len(data), false);
+        self.recv_clr(data, false);
+    }
+
+    /// Sends an encrypted message, like `send_enc(data, more)`, but refuses to do so if the state
+    /// has never been keyed. Encrypting under an unkeyed state produces output that anyone who
+    /// knows the transcript can decrypt.
+    ///
+    /// Returns `StrobeError::Unkeyed`, without modifying the state or `data`, if
+    /// [`Strobe::is_keyed`] is false.
+    pub fn send_enc_checked(&mut self, data: &mut [u8], more: bool) -> Result<(), StrobeError> {
+        if !self.keyed {
+            return Err(StrobeError::Unkeyed);
+        }
+        self.send_enc(data, more);
+        Ok(())
+    }
+
+    /// Receives an encrypted message, like `recv_enc(ct, more)`, but first checks that `ct` is
+    /// exactly `expected_len` bytes long, e.g., the length given by a frame header. This catches
+    /// truncated or padded ciphertexts before anything is decrypted.
+    ///
+    /// Returns `StrobeError::LengthMismatch`, without modifying the state or `ct`, if the lengths
+    /// differ.
+    pub fn recv_enc_exact(
+        &mut self,
+        ct: &mut [u8],
+        expected_len: usize,
+        more: bool,
+    ) -> Result<(), StrobeError> {
+        if ct.len() != expected_len {
+            return Err(StrobeError::LengthMismatch);
+        }
+        self.recv_enc(ct, more);
+        Ok(())
+    }
+
+    /// Sends a MAC of the internal state of length `len`, and returns it. This is `send_mac` on a
+    /// `len`-byte buffer, but refuses to make MACs that are too short to be secure.
+    ///
+    /// A MAC of `len` bytes can be forged with probability `2^(-8 * len)` per attempt, so a short
+    /// MAC is trivially forgeable. Returns `StrobeError::MacTooShort`, without modifying the
+    /// state, if `len` is less than [`MIN_MAC_LEN`]. To deliberately make a shorter MAC, use
+    /// [`Strobe::send_mac_allow_short`].
+    #[cfg(feature = "alloc")]
+    pub fn send_mac_checked(&mut self, len: usize) -> Result<Vec<u8>, StrobeError> {
+        if len < MIN_MAC_LEN {
+            return Err(StrobeError::MacTooShort);
+        }
+        Ok(self.send_mac_allow_short(len))
+    }
+
+    /// Sends a MAC of the internal state of length `len`, and returns it. Unlike
+    /// [`Strobe::send_mac_checked`], this places no lower bound on `len`. Only use this if you
+    /// know what you're doing.
+    #[cfg(feature = "alloc")]
+    pub fn send_mac_allow_short(&mut self, len: usize) -> Vec<u8> {
+        let mut mac = vec![0u8; len];
+        self.send_mac(&mut mac, /* more */ false);
+        mac
+    }
+
+    /// Encrypts and authenticates `msg` into a self-delimiting frame of the form
+    /// `len || ciphertext || mac`, where `len` is the length of `msg` as an 8-byte big-endian
+    /// integer, and `mac` is 16 bytes. This runs `meta_ad(len)`, then `send_enc` on `msg`, then
+    /// `send_mac`. The receiver should call `open_framed`.
+    ///
+    /// Binding the length into the transcript means a truncated frame fails to open.
+    #[cfg(feature = "alloc")]
+    pub fn seal_framed(&mut self, msg: &[u8]) -> Vec<u8> {
+        let len = (msg.len() as u64).to_be_bytes();
+        let mut frame = Vec::with_capacity(len.len() + msg.len() + FRAMED_MAC_LEN);
+        frame.extend_from_slice(&len);
+        frame.extend_from_slice(msg);
+        frame.resize(len.len() + msg.len() + FRAMED_MAC_LEN, 0);
+
+        let (len, rest) = frame.split_at_mut(len.len());
+        let (ciphertext, mac) = rest.split_at_mut(msg.len());
+        self.meta_ad(len, false);
+        self.send_enc(ciphertext, false);
+        self.send_mac(mac, false);
+
+        frame
+    }
+
+    /// Opens a frame made by `seal_framed`, returning the plaintext. Returns
+    /// `StrobeError::MacMismatch` if the MAC does not verify, or if the declared length does not
+    /// match the length of the frame.
+    ///
+    /// If the frame is too short or its declared length is inconsistent, this returns an error
+    /// without modifying the state. Otherwise, the state is modified even if the MAC check fails,
+    /// and the unauthenticated plaintext is zeroized before the error is returned.
+    #[cfg(feature = "alloc")]
+    pub fn open_framed(&mut self, frame: &[u8]) -> Result<Vec<u8>, StrobeError> {
+        const LEN_LEN: usize = 8;
+        if frame.len() < LEN_LEN + FRAMED_MAC_LEN {
+            return Err(StrobeError::MacMismatch);
+        }
+        let (len, rest) = frame.split_at(LEN_LEN);
+        let (ciphertext, mac) = rest.split_at(rest.len() - FRAMED_MAC_LEN);
+
+        // Check the declared length before touching the state
+        let declared_len = u64::from_be_bytes(len.try_into().unwrap());
+        if declared_len != ciphertext.len() as u64 {
+            return Err(StrobeError::MacMismatch);
+        }
+
+        self.meta_ad(len, false);
+        let mut plaintext = self.recv_enc_to_vec(ciphertext, false);
+        match self.recv_mac::<FRAMED_MAC_LEN>(mac.try_into().unwrap()) {
+            Ok(()) => Ok(plaintext),
+            Err(e) => {
+                // Don't leave unauthenticated plaintext lying around
+                plaintext.zeroize();
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Encrypts and authenticates `msg` under `key` and `nonce`, and returns `ciphertext || mac`,
+    /// where `mac` is 16 bytes. The operations are, in order: `key(key)`, `ad(nonce)`,
+    /// `send_enc` on `msg`, and `send_mac`. The receiver should call `decrypt` on a `Strobe` in
+    /// the same state, with the same key and nonce.
+    ///
+    /// A nonce MUST NOT be reused under the same key and state. Reusing one reveals the XOR of
+    /// the two plaintexts.
+    #[cfg(feature = "alloc")]
+    pub fn encrypt(&mut self, key: &[u8], nonce: &[u8], msg: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(msg.len() + FRAMED_MAC_LEN);
+        out.extend_from_slice(msg);
+        out.resize(msg.len() + FRAMED_MAC_LEN, 0);
+
+        let (ciphertext, mac) = out.split_at_mut(msg.len());
+        self.key(key, false);
+        self.ad(nonce, false);
+        self.send_enc(ciphertext, false);
+        self.send_mac(mac, false);
+
+        out
+    }
+
+    /// Decrypts and verifies `ct`, the output of `encrypt`, under `key` and `nonce`, and returns
+    /// the plaintext. The operations are, in order: `key(key)`, `ad(nonce)`, `recv_enc` on the
+    /// ciphertext, and `recv_mac` on the 16-byte `mac`.
+    ///
+    /// Returns `StrobeError::MacMismatch` if the MAC does not verify, in which case no plaintext
+    /// is returned. If `ct` is too short to hold a MAC, this returns the same error without
+    /// modifying the state. Otherwise, the state is modified even if the MAC check fails.
+    #[cfg(feature = "alloc")]
+    pub fn decrypt(&mut self, key: &[u8], nonce: &[u8], ct: &[u8]) -> Result<Vec<u8>, StrobeError> {
+        if ct.len() < FRAMED_MAC_LEN {
+            return Err(StrobeError::MacMismatch);
+        }
+        let (ciphertext, mac) = ct.split_at(ct.len() - FRAMED_MAC_LEN);
+
+        self.key(key, false);
+        self.ad(nonce, false);
+        let mut plaintext = self.recv_enc_to_vec(ciphertext, false);
+        if let Err(e) = self.recv_mac::<FRAMED_MAC_LEN>(mac.try_into().unwrap()) {
+            // Don't leave unauthenticated plaintext lying around
+            plaintext.zeroize();
+            return Err(e.into());
+        }
+
+        Ok(plaintext)
+    }
+
+    /// Returns a 32-byte tag summarizing the transcript so far, leaving `self` unmodified. This
+    /// runs `meta_ad(b"transcript-tag")` and a 32-byte `prf` on a copy of the state.
+    ///
+    /// Equal tags imply, with overwhelming probability, equal transcripts, and unequal tags prove
+    /// that the transcripts diverged. This is a debugging and commitment helper, e.g., for
+    /// differential testing. It is **not** a substitute for `send_mac`/`recv_mac`, and comparing
+    /// tags with `==` is not constant-time.
+    pub fn transcript_tag(&self) -> [u8; 32] {
+        let mut s = self.clone();
+        s.meta_ad(b"transcript-tag", false);
+
+        let mut tag = [0u8; 32];
+        s.prf(&mut tag, false);
+        tag
+    }
+
+    /// Binds the transcript of `other` into this one, e.g., to commit a parent protocol to the full
+    /// history of a sub-protocol. This runs `meta_ad` on `other.transcript_tag()`. `other` is not
+    /// modified.
+    ///
+    /// The binding is one-way: `self` now depends on everything `other` has done so far, but
+    /// `other` knows nothing about `self`, and nothing `other` does afterwards affects `self`.
+    pub fn bind(&mut self, other: &Strobe) {
+        self.meta_ad(other.transcript_tag(), /* more */ false);
+    }
+
+    /// Returns a checkpoint of the transcript so far, for checking that a replayed run matches an
+    /// original one up to this point. This is exactly [`Strobe::transcript_tag`]: it forks the
+    /// state, runs `meta_ad(b"transcript-tag")` and a 32-byte `prf` on the fork, and leaves `self`
+    /// unmodified. So taking a checkpoint never changes the transcript, and the same sequence of
+    /// operations always gives the same checkpoint.
+    ///
+    /// To validate a replay, record the checkpoint at a known point in the original run, perform
+    /// the same operations in the replay, and compare checkpoints at the same point:
+    ///
+    /// ```rust
+    /// # use strobe_rs::{SecParam, Strobe};
+    /// fn run(s: &mut Strobe) {
+    ///     s.key(b"the key", false);
+    ///     s.ad(b"request 1", false);
+    /// }
+    ///
+    /// let mut original = Strobe::new(b"checkpoint example", SecParam::B256);
+    /// run(&mut original);
+    /// let saved = original.checkpoint();
+    ///
+    /// let mut replay = Strobe::new(b"checkpoint example", SecParam::B256);
+    /// run(&mut replay);
+    /// assert_eq!(replay.checkpoint(), saved);
+    /// ```
+    ///
+    /// As with `transcript_tag`, comparing checkpoints with `==` is not constant-time, and is not
+    /// a substitute for `send_mac`/`recv_mac`.
+    pub fn checkpoint(&self) -> [u8; 32] {
+        self.transcript_tag()
+    }
+
+    /// Derives a nonce for message number `counter` into `out`, leaving `self` unmodified. This
+    /// runs `meta_ad(b"nonce")`, `meta_ad` on the 8-byte little-endian `counter`, and `prf(out)` on
+    /// a copy of the state.
+    ///
+    /// Distinct counters give independent nonces, so the caller only has to make sure that a
+    /// counter value is **never reused** under the same key. The usual way to do this is to
+    /// increment the counter once per message.
+    pub fn derive_nonce(&self, counter: u64, out: &mut [u8]) {
+        let mut s = self.clone();
+        s.meta_ad(b"nonce", false);
+        s.meta_ad(counter.to_le_bytes(), false);
+        s.prf(out, false);
+    }
+
+    /// Writes a digest of everything absorbed so far into `out`, leaving `self` unmodified. This
+    /// runs `meta_ad(b"digest snapshot")` and `prf(out, false)` on a copy of the state, so `self`
+    /// can keep absorbing data afterwards, e.g., to commit to a prefix of an append-only log.
+    ///
+    /// Each snapshot is an independent fork of the session. Taking a snapshot does not perturb the
+    /// ongoing absorption, so the final state is the same whether or not snapshots were taken.
+    /// The label separates snapshots from the session's own `prf` output, so publishing a
+    /// snapshot does not reveal what a later `prf` on `self` would return.
+    pub fn digest_snapshot(&self, out: &mut [u8]) {
+        let mut s = self.clone();
+        s.meta_ad(b"digest snapshot", /* more */ false);
+        s.prf(out, /* more */ false);
+    }
+
+    /// Returns a copy of this `Strobe`, including its secret state. This is exactly
+    /// `Clone::clone`, but gives duplication of secret state a name that's easy to search for
+    /// when auditing a codebase.
+    ///
+    /// Both copies are wiped independently when dropped, so cloning is safe, but every copy is
+    /// one more place the secret state lives. Prefer this over an implicit `.clone()` wherever the
+    /// state may be keyed.
+    pub fn secret_clone(&self) -> Strobe {
+        self.clone()
+    }
+
+    /// Returns a fluent wrapper around this `Strobe`, whose methods can be chained to write a
+    /// transcript as a single expression. See [`Chain`].
+    pub fn chain(&mut self) -> Chain<'_> {
+        Chain::new(self)
+    }
+
+    /// Encrypts the buffer `msg` in place with `send_enc(msg, false)`, and returns it. This is for
+    /// passing owned buffers, e.g., arrays or `Vec`s, through without an intermediate binding.
+    pub fn send_enc_owned<B: AsMut<[u8]>>(&mut self, mut msg: B) -> B {
+        self.send_enc(msg.as_mut(), /* more */ false);
+        msg
+    }
+
+    /// Decrypts the buffer `msg` in place with `recv_enc(msg, false)`, and returns it. This is for
+    /// passing owned buffers, e.g., arrays or `Vec`s, through without an intermediate binding.
+    pub fn recv_enc_owned<B: AsMut<[u8]>>(&mut self, mut msg: B) -> B {
+        self.recv_enc(msg.as_mut(), /* more */ false);
+        msg
+    }
+
+    /// Receives an encrypted message and returns the plaintext, leaving `ciphertext` untouched.
+    /// This copies `ciphertext` and runs `recv_enc` on the copy, so the output and resulting state
+    /// are identical to those of `recv_enc`.
+    #[cfg(feature = "alloc")]
+    pub fn recv_enc_to_vec(&mut self, ciphertext: &[u8], more: bool) -> Vec<u8> {
+        let mut buf = ciphertext.to_vec();
+        self.recv_enc(&mut buf, more);
+        buf
+    }
+
+    /// Writes keystream bytes into `out`, for use as the XOR pad in a counter-mode style
+    /// construction where the caller manages the data themselves.
+    ///
+    /// The initial contents of `out` are ignored and overwritten. The state transition is exactly
+    /// that of `prf(out, false)`: the squeezed bytes are zeroed in the state, so the keystream
+    /// cannot be recovered from later states. This differs from `send_enc` in that no plaintext
+    /// is folded into the state, so the keystream depends only on the state before the call.
+    /// Consequently, nothing here authenticates the data it's XORed with.
+    pub fn keystream(&mut self, out: &mut [u8]) {
+        self.prf(out, /* more */ false)
+    }
+
+    /// Writes the keystream bytes starting at byte `offset` into `out`, leaving `self` unmodified.
+    /// This makes the keystream seekable, e.g., for random-access or parallel decryption.
+    ///
+    /// The keystream is divided into blocks of [`KEYSTREAM_BLOCK_LEN`] bytes. Block `i` is the
+    /// output of `meta_ad(b"keystream", false)`, then `meta_ad(&i.to_le_bytes(), false)`, then a
+    /// `KEYSTREAM_BLOCK_LEN`-byte `prf`, run on a copy of the state. The label keeps the blocks
+    /// apart from other uses of an 8-byte `meta_ad`, like the length prefixes of the `*_framed`
+    /// methods. Consequently, the output of `keystream_at` does not
+    /// match that of `keystream`. As with `keystream`, nothing here authenticates the data the
+    /// keystream is XORed with.
+    ///
+    /// Panics if the requested range extends past block index `u64::MAX`.
+    pub fn keystream_at(&self, offset: u64, out: &mut [u8]) {
+        let block_len = KEYSTREAM_BLOCK_LEN as u64;
+        let mut block_idx = offset / block_len;
+        // Only the first block can start partway through
+        let mut skip = (offset % block_len) as usize;
+
+        let mut block = [0u8; KEYSTREAM_BLOCK_LEN];
+        let mut out = out;
+        while !out.is_empty() {
+            let mut s = self.clone();
+            s.meta_ad(b"keystream", false);
+            s.meta_ad(block_idx.to_le_bytes(), false);
+            s.prf(&mut block, false);
+
+            let n = core::cmp::min(out.len(), KEYSTREAM_BLOCK_LEN - skip);
+            let (head, rest) = out.split_at_mut(n);
+            head.copy_from_slice(&block[skip..skip + n]);
+            out = rest;
+
+            skip = 0;
+            if !out.is_empty() {
+                block_idx = block_idx.checked_add(1).expect("keystream offset overflow");
+            }
+        }
+
+        block.zeroize();
+    }
+
+    /// Fills `dest` with pseudorandom bytes derived from the internal state. This is exactly
+    /// `prf(dest, false)`, named to read naturally when the `Strobe` is acting as an entropy
+    /// source. Unlike `StrobeRng`, this does not take ownership of the `Strobe`.
+    pub fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.prf(dest, /* more */ false)
+    }
+
+    /// Extracts `N` bytes of pseudorandom data as a `GenericArray`. This is the same as
+    /// `prf(out, false)` on an `N`-byte buffer, and is meant for feeding keys into RustCrypto
+    /// types.
+    #[cfg(feature = "generic-array")]
+    pub fn prf_generic<N: ArrayLength<u8>>(&mut self) -> GenericArray<u8, N> {
+        let mut out = GenericArray::default();
+        self.prf(&mut out, /* more */ false);
+        out
+    }
+
+    /// Sends an `N`-byte MAC of the internal state as a `GenericArray`. This is the same as
+    /// `send_mac(out, false)` on an `N`-byte buffer.
+    #[cfg(feature = "generic-array")]
+    pub fn send_mac_generic<N: ArrayLength<u8>>(&mut self) -> GenericArray<u8, N> {
+        let mut out = GenericArray::default();
+        self.send_mac(&mut out, /* more */ false);
+        out
+    }
+
+    //
+    // These operations mix integers in as associated data. Both peers must use the same
+    // encoding, since mixing endiannesses will silently desynchronize the transcripts. For
+    // example:
+    //
+    //     s.ad_u64_le(5)    is the same as    s.ad(&[5, 0, 0, 0, 0, 0, 0, 0], false)
+    //
+
+    def_ad_int!(
+        ad_u64_le,
+        meta_ad_u64_le,
+        u64,
+        to_le_bytes,
+        "Mixes a `u64` into the internal state as associated data, encoded as 8 little-endian \
+         bytes. This is the same as passing `x.to_le_bytes()` to the corresponding AD \
+         operation.\n\n\
+         ```rust\n\
+         # use strobe_rs::{SecParam, Strobe};\n\
+         let mut s1 = Strobe::new(b\"ad_int example\", SecParam::B256);\n\
+         let mut s2 = s1.clone();\n\
+         s1.ad_u64_le(5);\n\
+         s2.ad(&[5, 0, 0, 0, 0, 0, 0, 0], false);\n\
+         assert_eq!(s1.transcript_tag(), s2.transcript_tag());\n\
+         ```"
+    );
+    def_ad_int!(
+        ad_u32_le,
+        meta_ad_u32_le,
+        u32,
+        to_le_bytes,
+        "Mixes a `u32` into the internal state as associated data, encoded as 4 little-endian \
+         bytes. This is the same as passing `x.to_le_bytes()` to the corresponding AD \
+         operation."
+    );
+    def_ad_int!(
+        ad_u64_be,
+        meta_ad_u64_be,
+        u64,
+        to_be_bytes,
+        "Mixes a `u64` into the internal state as associated data, encoded as 8 big-endian \
+         bytes. This is the same as passing `x.to_be_bytes()` to the corresponding AD \
+         operation."
+    );
+    def_ad_int!(
+        ad_u32_be,
+        meta_ad_u32_be,
+        u32,
+        to_be_bytes,
+        "Mixes a `u32` into the internal state as associated data, encoded as 4 big-endian \
+         bytes. This is the same as passing `x.to_be_bytes()` to the corresponding AD \
+         operation."
+    );
+    def_ad_int!(
+        ad_u128_le,
+        meta_ad_u128_le,
+        u128,
+        to_le_bytes,
+        "Mixes a `u128`, e.g., a UUID, into the internal state as associated data, encoded as 16 \
+         little-endian bytes. This is the same as passing `x.to_le_bytes()` to the corresponding \
+         AD operation."
+    );
+    def_ad_int!(
+        ad_u128_be,
+        meta_ad_u128_be,
+        u128,
+        to_be_bytes,
+        "Mixes a `u128`, e.g., a UUID, into the internal state as associated data, encoded as 16 \
+         big-endian bytes. This is the same as passing `x.to_be_bytes()` to the corresponding AD \
+         operation."
+    );
+
+    /// Writes pseudorandom bytes derived from the internal state into the uninitialized buffer
+    /// `out`, and returns it as an initialized slice. The output and the resulting state are
+    /// identical to those of `prf(out, false)`, but the buffer doesn't need to be zeroed first.
+    pub fn prf_uninit<'a>(&mut self, out: &'a mut [MaybeUninit<u8>]) -> &'a mut [u8] {
+        // These are the prf flags. This mirrors what operate() does for them.
+        let flags = PRF_FLAGS;
+        self.validate_streaming(flags, /* more */ false);
+        self.begin_op(flags);
+        self.count_bytes(out.len());
+        #[cfg(feature = "trace")]
+        trace_op(flags, false, out.len());
+
+        // Like squeeze, but writing into uninitialized memory
+        let mut rest = &mut out[..];
+        while !rest.is_empty() {
+            let n = self.chunk_len(rest.len());
+            let (chunk, tail) = rest.split_at_mut(n);
+            let st_chunk = &mut self.st.0[self.pos..self.pos + n];
+            for (o, s) in chunk.iter_mut().zip(st_chunk.iter_mut()) {
+                o.write(*s);
+                *s = 0;
+            }
+
+            self.advance(n);
+            rest = tail;
+        }
+
+        // SAFETY: Every byte of `out` was initialized by the loop above, and `MaybeUninit<u8>` has
+        // the same layout as `u8`
+        unsafe { core::slice::from_raw_parts_mut(out.as_mut_ptr() as *mut u8, out.len()) }
+    }
+
+    /// Squeezes `total` bytes of pseudorandom output, and hands them to `f` one block at a time,
+    /// without any caller-provided buffer. The blocks are at most `rate` bytes long, and their
+    /// concatenation, as well as the resulting state, is identical to that of a one-shot
+    /// `prf(out, false)` with a `total`-byte `out`. If `total` is 0, `f` is never called.
+    ///
+    /// Each block is wiped from the stack buffer after `f` returns.
+    pub fn prf_for_each(&mut self, total: usize, mut f: impl FnMut(&[u8])) {
+        let mut buf = [0u8; KECCAK_BLOCK_BYTES];
+        let mut remaining = total;
+        let mut more = false;
+        while remaining > 0 || !more {
+            let n = core::cmp::min(self.rate, remaining);
+            self.prf(&mut buf[..n], more);
+            if n > 0 {
+                f(&buf[..n]);
+            }
+
+            remaining -= n;
+            more = true;
+        }
+        buf.zeroize();
+    }
+
+    /// Draws a pseudorandom `u64`. This squeezes 8 bytes of PRF output and interprets them as a
+    /// **little-endian** integer. The state advances exactly as it would for
+    /// `prf(&mut [0u8; 8], false)`.
+    pub fn prf_u64(&mut self) -> u64 {
+        let mut buf = [0u8; 8];
+        self.prf(&mut buf, /* more */ false);
+        u64::from_le_bytes(buf)
+    }
+
+    /// Draws a pseudorandom `u64`. This squeezes 8 bytes of PRF output and interprets them as a
+    /// **big-endian** integer, e.g., to match an implementation in another language that reads
+    /// PRF output big-endian. The state advances exactly as it would for
+    /// `prf(&mut [0u8; 8], false)`.
+    pub fn prf_u64_be(&mut self) -> u64 {
+        let mut buf = [0u8; 8];
+        self.prf(&mut buf, /* more */ false);
+        u64::from_be_bytes(buf)
+    }
+
+    /// Draws a pseudorandom `u32`. This squeezes 4 bytes of PRF output and interprets them as a
+    /// **little-endian** integer. The state advances exactly as it would for
+    /// `prf(&mut [0u8; 4], false)`.
+    pub fn prf_u32(&mut self) -> u32 {
+        let mut buf = [0u8; 4];
+        self.prf(&mut buf, /* more */ false);
+        u32::from_le_bytes(buf)
+    }
+
     //
     // These operations mutate their inputs
     //
@@ -524,26 +2491,26 @@ impl Strobe {
     def_op_mut!(
         send_enc,
         meta_send_enc,
-        OpFlags::A | OpFlags::C | OpFlags::T,
+        SEND_ENC_FLAGS,
         "Sends an encrypted message."
     );
     def_op_mut!(
         recv_enc,
         meta_recv_enc,
-        OpFlags::I | OpFlags::A | OpFlags::C | OpFlags::T,
+        RECV_ENC_FLAGS,
         "Receives an encrypted message."
     );
     def_op_mut!(
         send_mac,
         meta_send_mac,
-        OpFlags::C | OpFlags::T,
+        SEND_MAC_FLAGS,
         "Sends a MAC of the internal state. \
          The output is independent of the initial contents of the input buffer."
     );
     def_op_mut!(
         prf,
         meta_prf,
-        OpFlags::I | OpFlags::A | OpFlags::C,
+        PRF_FLAGS,
         "Extracts pseudorandom data as a function of the internal state. \
          The output is independent of the initial contents of the input buffer."
     );
@@ -555,27 +2522,126 @@ impl Strobe {
     def_op_no_mut!(
         send_clr,
         meta_send_clr,
-        OpFlags::A | OpFlags::T,
+        SEND_CLR_FLAGS,
         "Sends a plaintext message."
     );
     def_op_no_mut!(
         recv_clr,
         meta_recv_clr,
-        OpFlags::I | OpFlags::A | OpFlags::T,
+        RECV_CLR_FLAGS,
         "Receives a plaintext message."
     );
     def_op_no_mut!(
         ad,
         meta_ad,
-        OpFlags::A,
+        AD_FLAGS,
         "Mixes associated data into the internal state."
     );
-    def_op_no_mut!(
-        key,
-        meta_key,
-        OpFlags::A | OpFlags::C,
-        "Sets a symmetric cipher key."
-    );
+    def_op_no_mut!(key, meta_key, KEY_FLAGS, "Sets a symmetric cipher key.");
+}
+
+/// The length of the MAC used by `Strobe::seal_framed` and `Strobe::open_framed`
+#[cfg(feature = "alloc")]
+const FRAMED_MAC_LEN: usize = 16;
+
+/// Checks whether `buf` is all zeros, in constant time. This accumulates the truth values of
+/// byte == 0.
+#[cfg(feature = "subtle")]
+fn ct_is_all_zero(buf: &[u8]) -> subtle::Choice {
+    let mut all_zero = subtle::Choice::from(1u8);
+    for b in buf {
+        all_zero &= b.ct_eq(&0u8);
+    }
+    all_zero
+}
+
+/// Checks whether `buf` is all zeros, in constant time
+#[cfg(feature = "subtle")]
+fn is_all_zero(buf: &[u8]) -> bool {
+    bool::from(ct_is_all_zero(buf))
+}
+
+/// Checks whether `buf` is all zeros, in best-effort constant time. This ORs all the bytes
+/// together and checks the result once at the end, so there is no data-dependent branch in the
+/// loop. Unlike the `subtle`-backed version, nothing stops the compiler from optimizing this
+/// into something that short-circuits, so this makes no hard guarantees.
+#[cfg(not(feature = "subtle"))]
+fn is_all_zero(buf: &[u8]) -> bool {
+    buf.iter().fold(0u8, |acc, b| acc | b) == 0
+}
+
+/// Logs the metadata of an operation, for debugging transcript desyncs between peers. This never
+/// logs the operation's data.
+#[cfg(feature = "trace")]
+fn trace_op(flags: OpFlags, more: bool, len: usize) {
+    log::trace!("strobe op: flags={:?} more={} len={}", flags, more, len);
+}
+
+/// Computes the CRC-32 (IEEE) of `data`. This is for detecting accidental corruption of serialized
+/// states, and is not cryptographic.
+#[cfg(all(feature = "serialize_secret_state", feature = "alloc"))]
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc ^= u32::from(b);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// Checks that `rate` leaves room in the state for the 2 bytes of padding that follow it
+fn is_valid_rate(rate: usize) -> bool {
+    rate >= 1 && rate + 2 <= KECCAK_BLOCK_BYTES
+}
+
+/// Encodes the length of `data` as the 8-byte little-endian length prefix used by the `*_framed`
+/// methods
+fn framed_len(data: &[u8]) -> [u8; 8] {
+    encode_framed_len(data.len() as u64)
+}
+
+/// Encodes a length as the 8-byte little-endian length prefix used by the `*_framed` methods. The
+/// length is always a `u64`, regardless of the target's pointer width, so that framing agrees
+/// between 32-bit and 64-bit peers.
+fn encode_framed_len(len: u64) -> [u8; 8] {
+    len.to_le_bytes()
+}
+
+/// Returns the rate, in bytes, of a `Strobe` with security parameter `sec`. This is
+/// `sec.rate(KECCAK_BLOCK_BYTES)`, usable in const contexts.
+pub const fn rate_for(sec: SecParam) -> usize {
+    KECCAK_BLOCK_BYTES - sec.bits() / 4 - 2
+}
+
+/// Returns the rate, in bytes, of a `Strobe` made by `new_with_security(proto, bits)`. Panics if
+/// `bits` is invalid, which is a compile-time error in const contexts.
+pub(crate) const fn rate_for_security(bits: usize) -> usize {
+    match rate_for_bits(bits, KECCAK_BLOCK_BYTES) {
+        Some(rate) => rate,
+        None => panic!("invalid security parameter"),
+    }
+}
+
+/// Hashes `data` into `out`, using `proto` as a domain separator. This is a convenience function
+/// that runs `Strobe::new(proto, SecParam::B256)`, then `ad(data, false)`, then `prf(out, false)`.
+///
+/// The output is deterministic, i.e., the same `proto`, `data`, and output length will always
+/// produce the same output.
+pub fn hash(proto: &[u8], data: &[u8], out: &mut [u8]) {
+    let mut s = Strobe::new(proto, SecParam::B256);
+    s.ad(data, false);
+    s.prf(out, false);
+}
+
+/// Hashes `data` into an `N`-byte array, using `proto` as a domain separator. This is the same
+/// as [`hash`], but returns its output by value.
+pub fn hash_array<const N: usize>(proto: &[u8], data: &[u8]) -> [u8; N] {
+    let mut out = [0u8; N];
+    hash(proto, data, &mut out);
+    out
 }
 
 #[test]
@@ -586,3 +2652,255 @@ fn version_str() {
     let s256 = Strobe::new(b"version_str test", SecParam::B256);
     assert_eq!(&s256.version_str(), b"Strobe-Keccak-256/1600-v1.0.2");
 }
+
+#[test]
+fn custom_sec_param() {
+    // A custom level that matches a standard one gives the same state
+    let s = Strobe::new_with_security(b"custom test", 128).unwrap();
+    assert_eq!(s.st.0, Strobe::new(b"custom test", SecParam::B128).st.0);
+
+    let s = Strobe::new_with_security(b"custom test", 192).unwrap();
+    assert_eq!(s.rate, 200 - 48 - 2);
+    assert_eq!(&s.version_str(), b"Strobe-Keccak-192/1600-v1.0.2");
+
+    // Too-small, non-multiples of 4, and too-large security levels are rejected
+    assert!(Strobe::new_with_security(b"custom test", 0).is_err());
+    assert!(Strobe::new_with_security(b"custom test", 96).is_err());
+    assert!(Strobe::new_with_security(b"custom test", 100).is_ok());
+    assert!(Strobe::new_with_security(b"custom test", 130).is_err());
+    assert!(Strobe::new_with_security(b"custom test", 788).is_ok());
+    assert!(Strobe::new_with_security(b"custom test", 792).is_err());
+}
+
+// Test that a streamed PRF produces the same output and state as a one-shot PRF, for every split
+// point. This includes splits that land exactly on the rate boundary, where `run_f` is called.
+#[test]
+fn streaming_prf_split_points() {
+    let mut base = Strobe::new(b"prfsplittest", SecParam::B256);
+    base.key(b"secretsauce", false);
+    let rate = base.rate;
+
+    for &len in &[1, rate - 1, rate, rate + 1, 2 * rate, 3 * rate + 1] {
+        let mut one_shot = base.clone();
+        let mut expected = [0u8; 1024];
+        one_shot.prf(&mut expected[..len], false);
+
+        for split in 0..=len {
+            let mut streamed = base.clone();
+            let mut out = [0u8; 1024];
+            let (first, second) = out[..len].split_at_mut(split);
+            streamed.prf(first, false);
+            streamed.prf(second, true);
+
+            assert_eq!(
+                &out[..len],
+                &expected[..len],
+                "len {}, split {}",
+                len,
+                split
+            );
+            assert_eq!(streamed.st.0, one_shot.st.0, "len {}, split {}", len, split);
+            assert_eq!(streamed.pos, one_shot.pos, "len {}, split {}", len, split);
+        }
+    }
+}
+
+#[test]
+fn raw_parts() {
+    let mut s = Strobe::new(b"rawpartstest", SecParam::B256);
+    s.key(b"secretsauce", false);
+    s.send_clr(b"hello", false);
+
+    // Round trip, then make sure the two copies stay in sync
+    let mut s_copy = s.clone();
+    let (st, sec, pos, pos_begin, is_receiver) = s_copy.clone().into_raw_parts();
+    let mut resumed = Strobe::from_raw_parts(st, sec, pos, pos_begin, is_receiver).unwrap();
+    let mut buf1 = [0u8; 300];
+    let mut buf2 = [0u8; 300];
+    s_copy.prf(&mut buf1, false);
+    resumed.prf(&mut buf2, false);
+    assert_eq!(buf1, buf2);
+    assert_eq!(s_copy.st.0, resumed.st.0);
+
+    // Impossible positions are rejected
+    let rate = s.rate;
+    let (st, ..) = s.into_raw_parts();
+    let res = Strobe::from_raw_parts(st, SecParam::B256, rate, 0, None);
+    assert_eq!(res.unwrap_err(), StrobeError::InvalidState);
+    let res = Strobe::from_raw_parts(st, SecParam::B256, 3, 4, None);
+    assert_eq!(res.unwrap_err(), StrobeError::InvalidState);
+}
+
+// Non-standard security levels have no SecParam, so they can't be decomposed
+#[test]
+#[should_panic]
+fn raw_parts_custom_sec() {
+    let s = Strobe::new_with_security(b"rawpartstest", 192).unwrap();
+    let _ = s.into_raw_parts();
+}
+
+// The raw sponge calls are exactly an op without its begin_op framing
+#[cfg(feature = "unstable-sponge")]
+#[test]
+fn raw_sponge() {
+    let mut s1 = Strobe::new(b"sponge test", SecParam::B256);
+    let mut s2 = s1.clone();
+
+    let pos_begin = s1.pos_begin as u8;
+    s1.ad(b"some data", false);
+    s2.sponge_absorb(&[pos_begin, AD_FLAGS.bits()]);
+    s2.sponge_absorb(b"some data");
+    assert_eq!(s1.st.0, s2.st.0);
+    assert_eq!(s1.pos, s2.pos);
+
+    // Squeezing reads from the current position, without forcing a permutation like prf does
+    let pos = s2.pos;
+    let expected = s2.st.0;
+    let mut out = [0u8; 10];
+    s2.sponge_squeeze(&mut out);
+    assert_eq!(out, expected[pos..pos + 10]);
+    assert_eq!(s2.st.0[pos..pos + 10], [0u8; 10]);
+}
+
+#[cfg(feature = "expose-state")]
+#[test]
+fn state_bytes_borrows() {
+    let mut s = Strobe::new(b"state_bytes test", SecParam::B256);
+    s.key(b"secretsauce", false);
+    assert_eq!(s.state_bytes(), &s.st.0);
+    assert!(core::ptr::eq(s.state_bytes(), &s.st.0));
+}
+
+#[test]
+fn overwrite_mode() {
+    let mut s = Strobe::new(b"overwritetest", SecParam::B256);
+    s.key(b"secretsauce", false);
+    s.recv_clr(b"hello", false);
+
+    // Overwriting with the recv_enc flags updates the state like recv_enc, but leaves the buffer
+    let ct = *b"some ciphertext";
+    let mut s1 = s.clone();
+    let mut s2 = s.clone();
+    let mut pt = ct;
+    s1.recv_enc(&mut pt, false);
+    s2.operate_no_mutate(RECV_ENC_FLAGS, &ct, false);
+    assert_ne!(pt, ct);
+    assert_eq!(s1.st.0, s2.st.0);
+    assert_eq!(s1.pos, s2.pos);
+
+    // Overwritten state bytes are exactly the data. Absorbed ones aren't. Ops with C run the
+    // permutation in begin_op, so their data starts at the beginning of the block.
+    let mut s3 = s.clone();
+    s3.operate_no_mutate(RECV_ENC_FLAGS, &ct, false);
+    assert_eq!(&s3.st.0[..ct.len()], &ct[..]);
+
+    let mut s4 = s;
+    let start = s4.pos + 2;
+    s4.operate_no_mutate(RECV_CLR_FLAGS, &ct, false);
+    assert_ne!(&s4.st.0[start..start + ct.len()], &ct[..]);
+}
+
+// A byte-at-a-time transliteration of the duplex construction in the Python reference
+// implementation. This is only used to check the optimized code above.
+#[cfg(test)]
+struct ReferenceStrobe {
+    st: AlignedKeccakState,
+    rate: usize,
+    pos: usize,
+    pos_begin: usize,
+    is_receiver: Option<bool>,
+}
+
+#[cfg(test)]
+impl ReferenceStrobe {
+    fn from_strobe(s: &Strobe) -> ReferenceStrobe {
+        ReferenceStrobe {
+            st: s.st.clone(),
+            rate: s.rate,
+            pos: s.pos,
+            pos_begin: s.pos_begin,
+            is_receiver: s.is_receiver,
+        }
+    }
+
+    fn run_f(&mut self) {
+        self.st.0[self.pos] ^= self.pos_begin as u8;
+        self.st.0[self.pos + 1] ^= 0x04;
+        self.st.0[self.rate + 1] ^= 0x80;
+        keccakf_u8(&mut self.st);
+        self.pos = 0;
+        self.pos_begin = 0;
+    }
+
+    fn duplex(&mut self, data: &mut [u8], cbefore: bool, cafter: bool, force_f: bool) {
+        for b in data.iter_mut() {
+            if cbefore {
+                *b ^= self.st.0[self.pos];
+            }
+            self.st.0[self.pos] ^= *b;
+            if cafter {
+                *b = self.st.0[self.pos];
+            }
+            self.pos += 1;
+            if self.pos == self.rate {
+                self.run_f();
+            }
+        }
+        if force_f && self.pos != 0 {
+            self.run_f();
+        }
+    }
+
+    fn operate(&mut self, mut flags: OpFlags, data: &mut [u8]) {
+        if flags.contains(OpFlags::T) {
+            let is_op_receiving = flags.contains(OpFlags::I);
+            let is_receiver = *self.is_receiver.get_or_insert(is_op_receiving);
+            flags.set(OpFlags::I, is_receiver != is_op_receiving);
+        }
+        let old_pos_begin = self.pos_begin;
+        self.pos_begin = self.pos + 1;
+        let force_f = flags.contains(OpFlags::C);
+        self.duplex(
+            &mut [old_pos_begin as u8, flags.bits()],
+            false,
+            false,
+            force_f,
+        );
+
+        let cafter = flags & (OpFlags::C | OpFlags::I | OpFlags::T) == OpFlags::C | OpFlags::T;
+        let cbefore = flags.contains(OpFlags::C) && !cafter;
+        self.duplex(data, cbefore, cafter, false);
+    }
+}
+
+#[test]
+fn prf_then_c_ops_within_block() {
+    // Offsets are chosen to land the prf, and the op after it, at various points in the block,
+    // including right up against the rate boundary
+    for ad_len in [0, 1, 7, 8, 60, 150, 161, 163, 164, 165] {
+        for prf_len in [0, 1, 2, 5, 8, 13] {
+            for op_flags in [SEND_MAC_FLAGS, SEND_ENC_FLAGS] {
+                let mut s = Strobe::new(b"prf boundary", SecParam::B128);
+                s.ad(&[0xab; 165][..ad_len], false);
+                let mut reference = ReferenceStrobe::from_strobe(&s);
+
+                let mut prf_out = [0u8; 13];
+                let mut ref_prf_out = [0u8; 13];
+                s.prf(&mut prf_out[..prf_len], false);
+                reference.operate(PRF_FLAGS, &mut ref_prf_out[..prf_len]);
+                assert_eq!(prf_out, ref_prf_out);
+
+                // The reference, like the spec, expects send_mac's input to be all zeros
+                let fill = if op_flags == SEND_MAC_FLAGS { 0 } else { 0x3c };
+                let mut out = [fill; 20];
+                let mut ref_out = out;
+                s.operate(op_flags, &mut out, false);
+                reference.operate(op_flags, &mut ref_out);
+                assert_eq!(out, ref_out, "ad_len {}, prf_len {}", ad_len, prf_len);
+
+                assert_eq!(s.st.0[..], reference.st.0[..]);
+                assert_eq!((s.pos, s.pos_begin), (reference.pos, reference.pos_begin));
+            }
+        }
+    }
+}
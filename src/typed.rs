@@ -0,0 +1,121 @@
+use crate::strobe::{rate_for_security, AuthError, Strobe};
+
+use core::ops::Deref;
+
+// This defines an operation and meta-operation that forward to the inner Strobe
+macro_rules! def_typed_op {
+    ($name:ident, $meta_name:ident, $data_ty:ty) => {
+        #[doc = concat!("Forwards to [`Strobe::", stringify!($name), "`]")]
+        pub fn $name(&mut self, data: $data_ty, more: bool) {
+            self.0.$name(data, more)
+        }
+
+        #[doc = concat!("Forwards to [`Strobe::", stringify!($meta_name), "`]")]
+        pub fn $meta_name(&mut self, data: $data_ty, more: bool) {
+            self.0.$meta_name(data, more)
+        }
+    };
+}
+
+/// A [`Strobe`] whose security level, in bits, is fixed at compile time. This is a zero-cost
+/// wrapper around `Strobe`. The STROBE operations are forwarded to the inner `Strobe`, and its
+/// `&self` methods are available via `Deref`. For anything else, use [`TypedStrobe::into_inner`].
+///
+/// This lets a protocol pin its security level in its types, so that, e.g., a `Strobe128` cannot
+/// be passed where a `Strobe256` is expected. Use `Strobe` directly if the security level is only
+/// known at runtime.
+///
+/// An invalid `SEC` (see [`Strobe::new_with_security`]) is a compile-time error.
+///
+/// There is no mutable access to the inner `Strobe`, since it could be reinitialized at another
+/// security level:
+///
+/// ```compile_fail
+/// # use strobe_rs::{SecParam, Strobe128};
+/// let mut s = Strobe128::new(b"typed");
+/// s.reinit(b"typed", SecParam::B256);
+/// ```
+#[derive(Clone, Debug)]
+pub struct TypedStrobe<const SEC: usize>(Strobe);
+
+/// A [`TypedStrobe`] with 128 bits of security
+pub type Strobe128 = TypedStrobe<128>;
+
+/// A [`TypedStrobe`] with 256 bits of security
+pub type Strobe256 = TypedStrobe<256>;
+
+impl<const SEC: usize> TypedStrobe<SEC> {
+    /// The rate, in bytes, of this security level. Referencing this fails to compile if `SEC` is
+    /// invalid.
+    pub const RATE: usize = rate_for_security(SEC);
+
+    /// Makes a new `TypedStrobe` with the given protocol byte string
+    pub fn new(proto: &[u8]) -> TypedStrobe<SEC> {
+        // Force the validity check on SEC
+        let _ = Self::RATE;
+        TypedStrobe(Strobe::new_with_security(proto, SEC).unwrap())
+    }
+
+    /// Returns the underlying `Strobe`
+    pub fn into_inner(self) -> Strobe {
+        self.0
+    }
+
+    def_typed_op!(send_enc, meta_send_enc, &mut [u8]);
+    def_typed_op!(recv_enc, meta_recv_enc, &mut [u8]);
+    def_typed_op!(send_mac, meta_send_mac, &mut [u8]);
+    def_typed_op!(prf, meta_prf, &mut [u8]);
+    def_typed_op!(send_clr, meta_send_clr, impl AsRef<[u8]>);
+    def_typed_op!(recv_clr, meta_recv_clr, impl AsRef<[u8]>);
+    def_typed_op!(ad, meta_ad, impl AsRef<[u8]>);
+    def_typed_op!(key, meta_key, impl AsRef<[u8]>);
+    def_typed_op!(ratchet, meta_ratchet, usize);
+
+    /// Forwards to [`Strobe::recv_mac`]
+    pub fn recv_mac<const N: usize>(&mut self, mac: &[u8; N]) -> Result<(), AuthError> {
+        self.0.recv_mac(mac)
+    }
+
+    /// Forwards to [`Strobe::meta_recv_mac`]
+    pub fn meta_recv_mac<const N: usize>(&mut self, mac: &[u8; N]) -> Result<(), AuthError> {
+        self.0.meta_recv_mac(mac)
+    }
+}
+
+impl<const SEC: usize> Deref for TypedStrobe<SEC> {
+    type Target = Strobe;
+
+    fn deref(&self) -> &Strobe {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+use crate::strobe::SecParam;
+
+#[test]
+fn typed_matches_runtime() {
+    let mut typed = Strobe256::new(b"typed");
+    let mut runtime = Strobe::new(b"typed", SecParam::B256);
+
+    typed.ad(b"hello", false);
+    runtime.ad(b"hello", false);
+    assert_eq!(typed.transcript_tag(), runtime.transcript_tag());
+
+    // The forwarded ops interoperate with the runtime ones
+    let mut mac = [0u8; 16];
+    typed.send_mac(&mut mac, false);
+    assert!(runtime.recv_mac(&mac).is_ok());
+    assert_eq!(
+        core::str::from_utf8(&typed.version_str()).unwrap(),
+        "Strobe-Keccak-256/1600-v1.0.2"
+    );
+
+    assert_eq!(Strobe128::RATE, 166);
+    assert_eq!(Strobe256::RATE, 134);
+    assert_eq!(TypedStrobe::<192>::RATE, 150);
+    assert_eq!(
+        core::mem::size_of::<Strobe128>(),
+        core::mem::size_of::<Strobe>()
+    );
+}
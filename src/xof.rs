@@ -0,0 +1,117 @@
+use crate::{strobe::Strobe, typed::Strobe256};
+
+use digest::{crypto_common::BlockSizeUser, typenum::U134, ExtendableOutput, Update, XofReader};
+
+/// An extendable-output function (XOF) built on `Strobe`. Input is mixed in with `ad`, and output
+/// is read with `prf`. This implements the `digest` crate's `Update` and `ExtendableOutput`
+/// traits.
+///
+/// A `StrobeXof` always uses `SecParam::B256`, so that its advertised block size is exactly its
+/// rate. A single `update(data)` followed by reading `out.len()` bytes gives the same output as
+/// [`hash`](crate::hash) with the same `proto`. Splitting the input across multiple `update`
+/// calls, or the output across multiple `read` calls, does not change the result.
+#[derive(Clone, Debug)]
+pub struct StrobeXof {
+    strobe: Strobe,
+    // Whether an ad has been begun, i.e., whether the next update continues it
+    started: bool,
+}
+
+impl StrobeXof {
+    /// Makes a new `StrobeXof` with the given protocol string
+    pub fn new(proto: &[u8]) -> StrobeXof {
+        Strobe256::new(proto).into()
+    }
+}
+
+// Only a Strobe256 is accepted, since its rate is guaranteed to match the block size
+impl From<Strobe256> for StrobeXof {
+    fn from(strobe: Strobe256) -> StrobeXof {
+        StrobeXof {
+            strobe: strobe.into_inner(),
+            started: false,
+        }
+    }
+}
+
+/// The block size is the rate of a `SecParam::B256` `Strobe`, i.e., 134 bytes. It is only a
+/// buffering hint. Input of any length is accepted.
+impl BlockSizeUser for StrobeXof {
+    type BlockSize = U134;
+}
+
+impl Update for StrobeXof {
+    fn update(&mut self, data: &[u8]) {
+        self.strobe.ad(data, self.started);
+        self.started = true;
+    }
+}
+
+impl ExtendableOutput for StrobeXof {
+    type Reader = StrobeXofReader;
+
+    fn finalize_xof(mut self) -> StrobeXofReader {
+        // Absorb the empty string if nothing was absorbed, so that the output matches `hash`
+        if !self.started {
+            self.strobe.ad([], false);
+        }
+
+        StrobeXofReader {
+            strobe: self.strobe,
+            started: false,
+        }
+    }
+}
+
+/// The output reader of a [`StrobeXof`]. Each read continues a single streaming `prf`.
+#[derive(Clone, Debug)]
+pub struct StrobeXofReader {
+    strobe: Strobe,
+    // Whether a prf has been begun, i.e., whether the next read continues it
+    started: bool,
+}
+
+impl XofReader for StrobeXofReader {
+    fn read(&mut self, buffer: &mut [u8]) {
+        self.strobe.prf(buffer, self.started);
+        self.started = true;
+    }
+}
+
+#[test]
+fn incremental_xof() {
+    let data = b"the quick brown fox";
+
+    // One-shot input and output
+    let mut xof = StrobeXof::new(b"xof test");
+    xof.update(data);
+    let mut one_shot = [0u8; 300];
+    xof.finalize_xof().read(&mut one_shot);
+
+    // Incremental input and output
+    let mut xof = StrobeXof::new(b"xof test");
+    xof.update(&data[..4]);
+    xof.update(&data[4..]);
+    let mut reader = xof.finalize_xof();
+    let mut incremental = [0u8; 300];
+    for chunk in incremental.chunks_mut(7) {
+        reader.read(chunk);
+    }
+    assert_eq!(one_shot, incremental);
+
+    // And it should match hash
+    let mut hashed = [0u8; 300];
+    crate::hash(b"xof test", data, &mut hashed);
+    assert_eq!(one_shot, hashed);
+
+    // Including on empty input
+    let mut empty = [0u8; 32];
+    StrobeXof::new(b"xof test").finalize_xof().read(&mut empty);
+    assert_eq!(empty, crate::hash_array::<32>(b"xof test", b""));
+}
+
+// The advertised block size should be the rate of the underlying Strobe
+#[test]
+fn xof_block_size() {
+    assert_eq!(StrobeXof::block_size(), Strobe256::RATE);
+}